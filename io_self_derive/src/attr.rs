@@ -1,144 +1,818 @@
-use darling::{FromDeriveInput, FromField, FromVariant};
-use proc_macro2::{self, TokenStream};
-use quote::{quote, ToTokens};
-use std::str::FromStr;
-use syn::Type;
-
-#[derive(FromDeriveInput, Default)]
-#[darling(default, attributes(io_self), forward_attrs(allow, doc, cfg))]
+use darling::ast::NestedMeta;
+use darling::util::{Flag, SpannedValue};
+use darling::{FromDeriveInput, FromField, FromMeta, FromVariant};
+use proc_macro2::{self, Span, TokenStream};
+use quote::{quote, quote_spanned, ToTokens};
+use syn::punctuated::Punctuated;
+use syn::spanned::Spanned;
+use syn::{Expr, ExprArray, ExprLit, Ident, Lit, LitByte, Member, Meta, Path, Token, Type, WherePredicate};
+
+/// Defines an enum which is parsed from the value of an attribute, along with the tokens each
+/// variant generates. Values are case insensitive, may have several spellings, and can be given
+/// either as a string or as a bare identifier.
+macro_rules! string_attr {
+    ($(#[$meta:meta])* $name:ident, $what:literal {
+        $($(#[$variant_meta:meta])* $variant:ident: $($alias:literal)|+ => $tokens:expr,)+
+    }) => {
+        $(#[$meta])*
+        #[derive(Copy, Clone, PartialEq, Eq)]
+        pub enum $name {
+            $($(#[$variant_meta])* $variant,)+
+        }
+
+        impl FromMeta for $name {
+            fn from_string(value: &str) -> darling::Result<Self> {
+                match value.to_ascii_lowercase().as_str() {
+                    $($($alias)|+ => Ok($name::$variant),)+
+                    x => {
+                        let expected = [$($($alias),+),+].join("\", \"");
+                        Err(darling::Error::custom(format!(
+                            concat!("unknown ", $what, " {:?}, expected one of \"{}\""),
+                            x, expected,
+                        )))
+                    }
+                }
+            }
+
+            fn from_expr(expr: &Expr) -> darling::Result<Self> {
+                match expr {
+                    Expr::Path(path) => match path.path.get_ident() {
+                        Some(ident) => Self::from_string(&ident.to_string()).map_err(|err| err.with_span(ident)),
+                        None => Err(darling::Error::unexpected_expr_type(expr)),
+                    },
+                    Expr::Lit(lit) => Self::from_value(&lit.lit),
+                    _ => Err(darling::Error::unexpected_expr_type(expr)),
+                }
+            }
+        }
+
+        impl ToTokens for $name {
+            fn to_tokens(&self, tokens: &mut TokenStream) {
+                tokens.extend(match self {
+                    $($name::$variant => $tokens,)+
+                })
+            }
+        }
+    };
+}
+
+#[derive(FromDeriveInput, Default, Clone)]
+#[darling(default, attributes(io_self), forward_attrs(allow, doc, cfg), and_then = "Self::validate")]
 pub struct Opts {
-    endian: Option<String>,
+    endian: Option<Endian>,
+    #[darling(with = parse_value)]
     tag: Option<Type>,
+    tag_endian: Option<Endian>,
+    untagged: bool,
+    #[darling(with = parse_value)]
     length_prefix: Option<Type>,
+    bit_order: Option<BitOrder>,
+    #[darling(with = parse_value)]
+    from: Option<Type>,
+    #[darling(with = parse_value)]
+    try_from: Option<Type>,
+    #[darling(with = parse_value)]
+    into: Option<Type>,
+    transparent: bool,
+    bound: Bound,
+    #[darling(skip)]
+    mode: Mode,
 }
 
 impl Opts {
+    fn validate(self) -> darling::Result<Self> {
+        match (&self.tag, self.untagged) {
+            (Some(tag), true) => Err(darling::Error::custom("untagged enums cannot have a tag").with_span(tag)),
+            _ => Ok(self),
+        }
+    }
+
+    pub fn with_mode(mut self, mode: Mode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
 
     pub fn length_prefix_type(&self) -> Option<&Type> {
         self.length_prefix.as_ref()
     }
 
     pub fn endianness(&self) -> Option<Endian> {
-        match self.endian.as_ref()?.to_ascii_lowercase().as_ref() {
-            "little" | "le" | "l" => Some(Endian::Little),
-            "big" | "be" | "b" => Some(Endian::Big),
-            x => panic!("Unknown endian format: {:?}", x),
+        self.endian
+    }
+
+    pub fn trait_usage(&self, read: bool) -> Approach {
+        Approach {
+            read,
+            mode: self.mode,
+            endian: self.endianness(),
         }
     }
 
-    pub fn trait_usage(&self, read: bool) -> TokenStream {
-        match (read, self.endianness()) {
-            (false, None) => quote!(::io_self::WriteSelf),
-            (true, None) => quote!(::io_self::ReadSelf),
-            (false, Some(Endian::Little)) => quote!(
-                ::io_self::derive_util::WriteSelfEndian<::io_self::derive_util::LittleEndian>
-            ),
-            (true, Some(Endian::Little)) => {
-                quote!(::io_self::derive_util::ReadSelfEndian<::io_self::derive_util::LittleEndian>)
-            }
-            (false, Some(Endian::Big)) => {
-                quote!(::io_self::derive_util::WriteSelfEndian<::io_self::derive_util::BigEndian>)
-            }
-            (true, Some(Endian::Big)) => {
-                quote!(::io_self::derive_util::ReadSelfEndian<::io_self::derive_util::BigEndian>)
-            }
+    /// How the tag of an enum is read or written. The tag uses the enum's endianness unless it
+    /// has its own `tag_endian`.
+    pub fn tag_usage(&self, read: bool) -> Approach {
+        Approach {
+            read,
+            mode: self.mode,
+            endian: self.tag_endian.or(self.endian),
         }
     }
 
-    pub fn tag_type(&self) -> Option<&Type> {
-        self.tag.as_ref()
+    pub fn is_generic_endian(&self) -> bool {
+        self.endianness() == Some(Endian::Generic)
+    }
+
+    /// The trait implemented by the derive. This is `ReadSelf` or `WriteSelf` unless the
+    /// endianness is generic, in which case the endian traits are implemented for any byte order.
+    pub fn implemented_trait(&self, read: bool) -> Approach {
+        Approach {
+            read,
+            mode: self.mode,
+            endian: self.endianness().filter(|endian| *endian == Endian::Generic),
+        }
+    }
+
+    /// The endian trait for any byte order `__O`. Types which do not have a generic endianness
+    /// implement this by delegating to the implemented trait.
+    pub fn endian_trait(&self, read: bool) -> Approach {
+        Approach {
+            read,
+            mode: self.mode,
+            endian: Some(Endian::Generic),
+        }
+    }
+
+    /// Whether the type is a newtype which is read and written exactly like its only field.
+    pub fn is_transparent(&self) -> bool {
+        self.transparent
+    }
+
+    /// The `where` predicates which replace every inferred bound of the implementation, if any.
+    pub fn bound(&self, read: bool) -> Option<&[WherePredicate]> {
+        self.bound.predicates(read)
+    }
+
+    /// The type read in place of `Self` and converted with `From`, if any.
+    pub fn conversion_from(&self) -> Option<&Type> {
+        self.from.as_ref()
+    }
+
+    /// The type read in place of `Self` and converted with `TryFrom`, if any.
+    pub fn conversion_try_from(&self) -> Option<&Type> {
+        self.try_from.as_ref()
+    }
+
+    /// The type written in place of `Self` after converting a clone with `Into`, if any.
+    pub fn conversion_into(&self) -> Option<&Type> {
+        self.into.as_ref()
+    }
+
+    /// Whether the variants of an enum are distinguished by trying to read each in turn.
+    pub fn is_untagged(&self) -> bool {
+        self.untagged
+    }
+
+    /// The type of the tag used to distinguish variants, which is required for enums.
+    pub fn tag_type(&self, name: &Ident) -> darling::Result<&Type> {
+        self.tag.as_ref().ok_or_else(|| {
+            darling::Error::custom("enums require a `tag` type to distinguish variants")
+                .with_span(name)
+        })
+    }
+
+    /// The order of bits within groups of `bits` fields. Defaults to most significant bit first.
+    pub fn bit_order(&self) -> BitOrder {
+        self.bit_order.unwrap_or(BitOrder::MsbFirst)
     }
 }
 
-#[derive(FromVariant, Default)]
-#[darling(default, attributes(io_self), forward_attrs(allow, doc, cfg))]
+string_attr!(
+    /// The order of bits within a group of `bits` fields.
+    BitOrder, "bit order" {
+        MsbFirst: "msb" | "msb_first" => quote!(::io_self::bits::BitOrder::MsbFirst),
+        LsbFirst: "lsb" | "lsb_first" => quote!(::io_self::bits::BitOrder::LsbFirst),
+    }
+);
+
+#[derive(FromVariant)]
+#[darling(attributes(io_self), forward_attrs(allow, doc, cfg))]
 pub struct VariantOpts {
-    tag: String,
+    ident: Ident,
+    #[darling(with = parse_value)]
+    tag: Option<Expr>,
+    endian: Option<SpannedValue<Endian>>,
+    #[darling(with = parse_value)]
+    length_prefix: Option<Type>,
+    #[darling(with = parse_value)]
+    magic: Option<Expr>,
+    pad_after: Option<usize>,
+    #[darling(with = parse_value)]
+    validate: Option<Expr>,
 }
 
 impl VariantOpts {
-    pub fn tag(&self) -> TokenStream {
-        TokenStream::from_str(&self.tag).unwrap()
+    /// The container options used for the fields of this variant, with its own endianness in
+    /// place of the enum's.
+    pub fn apply(&self, opts: &Opts) -> darling::Result<Opts> {
+        let mut opts = opts.clone();
+        if let Some(endian) = &self.endian {
+            if **endian == Endian::Generic && !opts.is_generic_endian() {
+                return Err(darling::Error::custom("variants can only have a generic endianness when the enum does")
+                    .with_span(&endian.span()));
+            }
+            opts.endian = Some(**endian);
+        }
+        Ok(opts)
+    }
+
+    /// The type of the length prefix before the body of the variant, which follows the tag.
+    pub fn length_prefix_type(&self) -> Option<&Type> {
+        self.length_prefix.as_ref()
+    }
+
+    /// Bytes which must appear before the fields of the variant (Ex: `magic = b"IHDR"`).
+    pub fn magic(&self) -> Option<&Expr> {
+        self.magic.as_ref()
+    }
+
+    /// The number of bytes skipped after the fields of the variant, which are written as zeros.
+    pub fn pad_after(&self) -> Option<usize> {
+        self.pad_after
+    }
+
+    /// A condition on the fields of the variant which must hold once it has been read. Fields are
+    /// referred to by name, or as `self.0` in tuple variants. This is only checked when reading,
+    /// since the fields are borrowed while writing and a condition such as `count <= 3` would not
+    /// apply to them.
+    pub fn validate(&self) -> Option<&Expr> {
+        self.validate.as_ref()
+    }
+
+    /// The value of the tag which selects this variant, which is required unless the enum is
+    /// untagged. This is used both as a pattern and as a value, so byte strings are expanded into
+    /// arrays of bytes.
+    pub fn tag(&self) -> darling::Result<Expr> {
+        self.tag
+            .as_ref()
+            .map(expand_byte_strings)
+            .ok_or_else(|| darling::Error::missing_field("tag").with_span(&self.ident))
+    }
+
+    /// Variants of an untagged enum are told apart by their contents, so they cannot have a tag.
+    pub fn check_untagged(&self) -> darling::Result<()> {
+        match &self.tag {
+            Some(tag) => Err(darling::Error::custom("variants of untagged enums cannot have a tag").with_span(tag)),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Replaces byte strings within a tag with arrays (Ex: `b"RIFF"` becomes `[b'R', b'I', b'F', b'F']`),
+/// since a byte string is a reference to an array and can not be matched against an array tag.
+fn expand_byte_strings(expr: &Expr) -> Expr {
+    match expr {
+        Expr::Lit(ExprLit { lit: Lit::ByteStr(bytes), .. }) => {
+            let bytes = bytes.value().into_iter().map(|byte| LitByte::new(byte, bytes.span()));
+            Expr::Array(ExprArray {
+                attrs: Vec::new(),
+                bracket_token: Default::default(),
+                elems: bytes.map(|byte| Expr::Lit(ExprLit { attrs: Vec::new(), lit: Lit::Byte(byte) })).collect(),
+            })
+        }
+        Expr::Tuple(tuple) => {
+            let mut tuple = tuple.clone();
+            tuple.elems = tuple.elems.iter().map(expand_byte_strings).collect();
+            Expr::Tuple(tuple)
+        }
+        Expr::Paren(paren) => expand_byte_strings(&paren.expr),
+        expr => expr.clone(),
     }
 }
 
-#[derive(Copy, Clone)]
-pub enum Endian {
-    Little,
-    Big,
+/// Whether the derive is generating a blocking or an asynchronous implementation.
+#[derive(Copy, Clone, Default, PartialEq, Eq)]
+pub enum Mode {
+    #[default]
+    Blocking,
+    Async,
 }
 
-impl ToTokens for Endian {
+/// The trait used to read or write a value along with how it should be called.
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub struct Approach {
+    read: bool,
+    mode: Mode,
+    endian: Option<Endian>,
+}
+
+impl Approach {
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    /// The byte order type parameter for the endian traits, or `()` when none was specified.
+    pub fn endian_type(&self) -> TokenStream {
+        match self.endian {
+            Some(endian) => quote!(#endian),
+            None => quote!(()),
+        }
+    }
+
+    pub fn read_call(&self, ty: &Type) -> TokenStream {
+        match self.mode {
+            Mode::Blocking => quote_spanned!(ty.span() => <#ty as #self>::read_from(__buffer)?),
+            Mode::Async => {
+                quote_spanned!(ty.span() => <#ty as #self>::read_from_async(__buffer).await?)
+            }
+        }
+    }
+
+    pub fn write_call(&self, ty: &Type, name: &TokenStream) -> TokenStream {
+        match self.mode {
+            Mode::Blocking => quote_spanned!(ty.span() => <#ty as #self>::write_to(#name, __buffer)?;),
+            Mode::Async => {
+                quote_spanned!(ty.span() => <#ty as #self>::write_to_async(#name, __buffer).await?;)
+            }
+        }
+    }
+}
+
+impl ToTokens for Approach {
     fn to_tokens(&self, tokens: &mut TokenStream) {
-        match self {
-            Endian::Little => tokens.extend(quote!(io_self::derive_util::byteorder::LittleEndian)),
-            Endian::Big => tokens.extend(quote!(io_self::derive_util::byteorder::BigEndian)),
+        tokens.extend(match (self.mode, self.read, self.endian) {
+            (Mode::Blocking, false, None) => quote!(::io_self::WriteSelf),
+            (Mode::Blocking, true, None) => quote!(::io_self::ReadSelf),
+            (Mode::Blocking, false, Some(endian)) => {
+                quote!(::io_self::derive_util::WriteSelfEndian<#endian>)
+            }
+            (Mode::Blocking, true, Some(endian)) => {
+                quote!(::io_self::derive_util::ReadSelfEndian<#endian>)
+            }
+            (Mode::Async, false, None) => quote!(::io_self::AsyncWriteSelf),
+            (Mode::Async, true, None) => quote!(::io_self::AsyncReadSelf),
+            (Mode::Async, false, Some(endian)) => {
+                quote!(::io_self::derive_util::AsyncWriteSelfEndian<#endian>)
+            }
+            (Mode::Async, true, Some(endian)) => {
+                quote!(::io_self::derive_util::AsyncReadSelfEndian<#endian>)
+            }
+        })
+    }
+}
+
+string_attr!(Endian, "endian" {
+    Little: "little" | "le" | "l" => quote!(::io_self::derive_util::LittleEndian),
+    Big: "big" | "be" | "b" => quote!(::io_self::derive_util::BigEndian),
+    /// Chosen by the parent through the `__O` type parameter of the impl.
+    Generic: "generic" => quote!(__O),
+});
+
+string_attr!(Encoding, "encoding" {
+    Utf8: "utf8" | "utf-8" => quote!(::io_self::encoding::Encoding::Utf8),
+    Utf16Le: "utf16le" | "utf-16le" => quote!(::io_self::encoding::Encoding::Utf16Le),
+    Utf16Be: "utf16be" | "utf-16be" => quote!(::io_self::encoding::Encoding::Utf16Be),
+    Ucs2Le: "ucs2le" | "ucs-2le" => quote!(::io_self::encoding::Encoding::Ucs2Le),
+    Ucs2Be: "ucs2be" | "ucs-2be" => quote!(::io_self::encoding::Encoding::Ucs2Be),
+    Latin1: "latin1" | "iso-8859-1" => quote!(::io_self::encoding::Encoding::Latin1),
+    Cp437: "cp437" => quote!(::io_self::encoding::Encoding::Cp437),
+});
+
+string_attr!(ErrorPolicy, "encoding_errors policy" {
+    Error: "error" => quote!(::io_self::encoding::ErrorPolicy::Error),
+    Replace: "replace" => quote!(::io_self::encoding::ErrorPolicy::Replace),
+    Raw: "raw" => quote!(::io_self::encoding::ErrorPolicy::Raw),
+});
+
+string_attr!(LengthUnit, "length_unit" {
+    Bytes: "bytes" => quote!(::io_self::encoding::LengthUnit::Bytes),
+    CodeUnits: "code_units" => quote!(::io_self::encoding::LengthUnit::CodeUnits),
+});
+
+/// Parses the value of an attribute written either as Rust syntax (`length_prefix = u16`) or
+/// within a string (`length_prefix = "u16"`). Errors point inside the value in both cases, since
+/// tokens parsed from a string keep the span of the literal.
+fn parse_value<T: syn::parse::Parse>(meta: &Meta) -> darling::Result<Option<T>> {
+    let value = match meta {
+        Meta::NameValue(name_value) => &name_value.value,
+        _ => return Err(darling::Error::unsupported_format("list or word").with_span(meta)),
+    };
+
+    let parsed = match value {
+        Expr::Lit(ExprLit { lit: Lit::Str(lit), .. }) => lit.parse(),
+        expr => syn::parse2(expr.to_token_stream()),
+    };
+    Ok(Some(parsed?))
+}
+
+/// `where` predicates given by a `bound` attribute in place of the bounds the derive would infer.
+/// A single string (`bound = "T: ReadSelf"`) applies to every trait, while
+/// `bound(read = "...", write = "...")` sets the bounds of the read and write traits separately.
+#[derive(Default, Clone)]
+pub struct Bound {
+    read: Option<Predicates>,
+    write: Option<Predicates>,
+}
+
+impl Bound {
+    pub fn predicates(&self, read: bool) -> Option<&[WherePredicate]> {
+        let predicates = match read {
+            true => self.read.as_ref()?,
+            false => self.write.as_ref()?,
+        };
+        Some(&predicates.0)
+    }
+}
+
+impl FromMeta for Bound {
+    fn from_value(value: &Lit) -> darling::Result<Self> {
+        let predicates = Predicates::from_value(value)?;
+        Ok(Bound {
+            read: Some(predicates.clone()),
+            write: Some(predicates),
+        })
+    }
+
+    fn from_list(items: &[NestedMeta]) -> darling::Result<Self> {
+        #[derive(FromMeta)]
+        struct SplitBound {
+            read: Option<Predicates>,
+            write: Option<Predicates>,
+        }
+
+        let SplitBound { read, write } = SplitBound::from_list(items)?;
+        Ok(Bound { read, write })
+    }
+}
+
+/// A comma separated list of `where` predicates within a string. An empty string removes the
+/// bounds entirely.
+#[derive(Clone)]
+struct Predicates(Vec<WherePredicate>);
+
+impl FromMeta for Predicates {
+    fn from_value(value: &Lit) -> darling::Result<Self> {
+        match value {
+            Lit::Str(lit) => {
+                let predicates = lit.parse_with(Punctuated::<WherePredicate, Token![,]>::parse_terminated)?;
+                Ok(Predicates(predicates.into_iter().collect()))
+            }
+            _ => Err(darling::Error::unexpected_lit_type(value)),
+        }
+    }
+}
+
+/// The field named by `tag_from`, written either as `self.kind` or just `kind`. Fields of tuple
+/// structs are given by their index (Ex: `self.0`).
+pub struct TagSource(Member);
+
+impl FromMeta for TagSource {
+    fn from_expr(expr: &Expr) -> darling::Result<Self> {
+        let expr = match expr {
+            Expr::Lit(ExprLit { lit: Lit::Str(lit), .. }) => lit.parse()?,
+            expr => expr.clone(),
+        };
+
+        match &expr {
+            Expr::Field(field) if matches!(&*field.base, Expr::Path(base) if base.path.is_ident("self")) => {
+                Ok(TagSource(field.member.clone()))
+            }
+            Expr::Path(path) => match path.path.get_ident() {
+                Some(ident) => Ok(TagSource(Member::Named(ident.clone()))),
+                None => Err(darling::Error::unexpected_expr_type(&expr)),
+            },
+            _ => Err(darling::Error::custom("expected the name of a field, such as `self.kind`").with_span(&expr)),
+        }
+    }
+}
+
+/// The base position of a `FilePtr` field.
+pub enum OffsetBase {
+    Absolute,
+    Struct,
+    Expr(Box<Expr>),
+}
+
+impl FromMeta for OffsetBase {
+    fn from_expr(expr: &Expr) -> darling::Result<Self> {
+        match expr {
+            Expr::Lit(ExprLit { lit: Lit::Str(lit), .. }) => match lit.value().as_str() {
+                "absolute" => Ok(OffsetBase::Absolute),
+                "struct" => Ok(OffsetBase::Struct),
+                _ => Ok(OffsetBase::Expr(Box::new(lit.parse()?))),
+            },
+            Expr::Path(path) if path.path.is_ident("absolute") => Ok(OffsetBase::Absolute),
+            expr => Ok(OffsetBase::Expr(Box::new(expr.clone()))),
         }
     }
 }
 
 #[derive(FromField, Default)]
-#[darling(default, attributes(io_self), forward_attrs(allow, doc, cfg))]
+#[darling(default, attributes(io_self), forward_attrs(allow, doc, cfg), and_then = "Self::validate")]
 pub struct FieldOpts {
-    length_prefix: Option<String>,
-    endian: Option<String>,
-    read_fn: Option<String>,
-    write_fn: Option<String>,
+    #[darling(with = parse_value)]
+    length_prefix: Option<Type>,
+    endian: Option<SpannedValue<Endian>>,
+    #[darling(with = parse_value)]
+    read_fn: Option<Expr>,
+    #[darling(with = parse_value)]
+    read_with: Option<Expr>,
+    #[darling(with = parse_value)]
+    write_fn: Option<Expr>,
+    #[darling(with = parse_value)]
+    write_with: Option<Expr>,
+    #[darling(with = parse_value)]
+    with: Option<Path>,
+    #[darling(with = parse_value)]
+    args: Option<Expr>,
+    #[darling(with = parse_value)]
+    map: Option<Expr>,
+    #[darling(with = parse_value)]
+    try_map: Option<Expr>,
+    #[darling(with = parse_value)]
+    write_map: Option<Expr>,
+    offset_base: Option<OffsetBase>,
+    follow: bool,
+    encoding: Option<SpannedValue<Encoding>>,
+    encoding_errors: Option<ErrorPolicy>,
+    length_unit: Option<LengthUnit>,
+    null_terminated: Flag,
+    max_len: Option<SpannedValue<usize>>,
+    fixed_size: Option<SpannedValue<usize>>,
+    bits: Option<SpannedValue<u32>>,
+    bound: Bound,
+    tag_from: Option<TagSource>,
+    until_eof: Flag,
+    #[darling(skip)]
+    text: Option<TextLayout>,
+    #[darling(skip)]
+    mode: Mode,
+}
+
+/// How an encoded string field is delimited.
+pub enum TextLayout {
+    Prefixed(Box<Type>),
+    /// Terminated by a null code unit, which must be found within the maximum length in bytes.
+    Terminated(Option<usize>),
+    Fixed(usize),
 }
 
 impl FieldOpts {
+    /// `read_with` and `write_with` are alternate names for `read_fn` and `write_fn`. Encoded
+    /// strings must be delimited in exactly one way, and using `null_terminated` or `fixed_size`
+    /// without an encoding implies UTF-8. `max_len` bounds the length of a null terminated string
+    /// when it is read. Errors point at the attribute which conflicts.
+    fn validate(mut self) -> darling::Result<Self> {
+        self.read_fn = match (self.read_fn.take(), self.read_with.take()) {
+            (Some(_), Some(with)) => {
+                return Err(darling::Error::custom("read_fn and read_with cannot be used together").with_span(&with))
+            }
+            (func, with) => func.or(with),
+        };
+        self.write_fn = match (self.write_fn.take(), self.write_with.take()) {
+            (Some(_), Some(with)) => {
+                return Err(darling::Error::custom("write_fn and write_with cannot be used together").with_span(&with))
+            }
+            (func, with) => func.or(with),
+        };
+
+        if let Some(with) = &self.with {
+            if self.read_fn.is_some() || self.write_fn.is_some() {
+                return Err(darling::Error::custom("with cannot be used together with read_fn or write_fn").with_span(with));
+            }
+        }
+        if let Some(try_map) = &self.try_map {
+            if self.map.is_some() {
+                return Err(darling::Error::custom("map and try_map cannot be used together").with_span(try_map));
+            }
+        }
+        if let Some(args) = &self.args {
+            if self.with.is_none() {
+                return Err(darling::Error::custom("args can only be used with a `with` codec").with_span(args));
+            }
+        }
+        let custom = self.read_fn.is_some() || self.write_fn.is_some() || self.with.is_some() || self.is_mapped();
+        if let Some(TagSource(member)) = &self.tag_from {
+            if custom || self.bits.is_some() || self.encoding.is_some() {
+                return Err(darling::Error::custom("tag_from can only be used on a field which is an enum").with_span(member));
+            }
+        }
+
+        if self.until_eof.is_present()
+            && (custom || self.length_prefix.is_some() || self.bits.is_some() || self.encoding.is_some() || self.tag_from.is_some())
+        {
+            return Err(darling::Error::custom("until_eof can only be used on a Vec or Option field read with its own trait")
+                .with_span(&self.until_eof.span()));
+        }
+        if let Some(bits) = &self.bits {
+            if **bits == 0 {
+                return Err(darling::Error::custom("bits must be at least 1").with_span(&bits.span()));
+            }
+        }
+
+        let null_terminated = self.null_terminated.is_present();
+        if let Some(max_len) = &self.max_len {
+            if !null_terminated {
+                return Err(darling::Error::custom("max_len can only be used on a null_terminated string")
+                    .with_span(&max_len.span()));
+            }
+        }
+        if self.encoding.is_none() && !null_terminated && self.fixed_size.is_none() {
+            return Ok(self);
+        }
+
+        self.text = Some(match (&self.length_prefix, null_terminated, self.fixed_size.as_deref()) {
+            (Some(prefix), false, None) => TextLayout::Prefixed(Box::new(prefix.clone())),
+            (None, true, None) => TextLayout::Terminated(self.max_len.as_deref().copied()),
+            (None, false, Some(size)) => TextLayout::Fixed(*size),
+            _ => {
+                // Point at the last delimiter given, or at the encoding when there is none
+                let span = match (&self.fixed_size, null_terminated, &self.encoding) {
+                    (Some(size), _, _) => size.span(),
+                    (None, true, _) => self.null_terminated.span(),
+                    (None, false, encoding) => encoding.as_ref().map_or_else(Span::call_site, |encoding| encoding.span()),
+                };
+                return Err(darling::Error::custom(
+                    "encoded strings require exactly one of length_prefix, null_terminated or fixed_size",
+                )
+                .with_span(&span));
+            }
+        });
+        Ok(self)
+    }
+
     pub fn read_fn(&self) -> Option<TokenStream> {
-        let func = TokenStream::from_str(self.read_fn.as_ref()?)
-            .expect("Unable to tokenize read_fn");
+        let func = self.read_fn.as_ref()?;
 
-        Some(quote!{ (#func)(buffer)? })
+        Some(match self.mode {
+            Mode::Blocking => quote!{ (#func)(__buffer)? },
+            Mode::Async => quote!{ compile_error!("read_fn is not supported by AsyncReadSelf") },
+        })
     }
 
 
     pub fn write_fn(&self, name: &TokenStream) -> Option<TokenStream> {
-        let func = TokenStream::from_str(self.write_fn.as_ref()?)
-            .expect("Unable to tokenize read_fn");
+        let func = self.write_fn.as_ref()?;
+
+        Some(match self.mode {
+            Mode::Blocking => quote!{ (#func)(#name, __buffer)?; },
+            Mode::Async => quote!{ compile_error!("write_fn is not supported by AsyncWriteSelf"); },
+        })
+    }
+
+    /// The module of a `with` codec providing `read_from`, `write_to` and `encoded_len`.
+    pub fn codec(&self) -> Option<&Path> {
+        self.with.as_ref()
+    }
+
+    /// The arguments passed to a codec. These may refer to fields which have already been read,
+    /// and are borrowed when reading so that a field named by them is not moved. Fields are
+    /// already borrowed while writing, so both directions receive a reference to the field.
+    pub fn codec_args(&self, read: bool) -> TokenStream {
+        match (&self.args, read) {
+            (Some(args), true) => quote!(&(#args)),
+            (Some(args), false) => quote!(#args),
+            (None, _) => quote!(()),
+        }
+    }
+
+    /// Whether the field is stored as a different type, which is converted by `map` or `try_map`
+    /// when reading and by `write_map` when writing.
+    pub fn is_mapped(&self) -> bool {
+        self.map.is_some() || self.try_map.is_some()
+    }
+
+    /// The `map` or `try_map` conversion of the field, if any.
+    pub fn map_expr(&self) -> Option<&Expr> {
+        self.map.as_ref().or(self.try_map.as_ref())
+    }
+
+    /// Converts the value read from the stream with `map` or `try_map`. `context` names the field
+    /// in the error for a failed conversion.
+    pub fn map_read(&self, wire: TokenStream, context: &str) -> TokenStream {
+        match (&self.map, &self.try_map) {
+            (Some(map), _) => quote_spanned!(map.span() => ::io_self::derive_util::map_value(#wire, #map)),
+            (None, Some(map)) => quote_spanned!(map.span() =>
+                ::io_self::derive_util::try_map_value(#wire, #map, #context)?
+            ),
+            (None, None) => wire,
+        }
+    }
+
+    /// The value written in place of the field when it has a `write_map`.
+    pub fn write_map(&self, name: &TokenStream) -> Option<TokenStream> {
+        let map = self.write_map.as_ref()?;
+        Some(quote_spanned!(map.span() => ::io_self::derive_util::map_value(#name, #map)))
+    }
+
+    /// Whether the field is read (or written) with the trait of the derive, so the type parameters
+    /// it uses must implement that trait. Fields with a custom function, a codec, an encoding, a
+    /// width in bits or a conversion are handled without it.
+    pub fn uses_trait(&self, read: bool) -> bool {
+        let custom = match read {
+            true => self.read_fn.is_some() || self.is_mapped(),
+            false => self.write_fn.is_some() || self.write_map.is_some(),
+        };
+        !custom && self.with.is_none() && self.text.is_none() && self.bits.is_none() && self.tag_from.is_none()
+    }
+
+    /// The earlier field holding the tag of this enum field, given by `tag_from`.
+    pub fn tag_from(&self) -> Option<&Member> {
+        Some(&self.tag_from.as_ref()?.0)
+    }
+
+    /// The `where` predicates which replace the bounds inferred from this field, if any.
+    pub fn bound(&self, read: bool) -> Option<&[WherePredicate]> {
+        self.bound.predicates(read)
+    }
+
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    /// The base position for a `FilePtr` field. This can either be `absolute`, `struct` for the
+    /// position at the start of the struct, or an expression which may use previous fields.
+    pub fn offset_base(&self) -> Option<TokenStream> {
+        match self.offset_base.as_ref()? {
+            OffsetBase::Absolute => None,
+            OffsetBase::Struct => Some(quote!(__start)),
+            OffsetBase::Expr(expr) => Some(quote!(::std::convert::Into::<u64>::into(#expr))),
+        }
+    }
+
+    pub fn uses_struct_start(&self) -> bool {
+        matches!(self.offset_base, Some(OffsetBase::Struct))
+    }
+
+    pub fn bits(&self) -> Option<u32> {
+        self.bits.as_deref().copied()
+    }
+
+    pub fn follow(&self) -> bool {
+        self.follow
+    }
+
+    /// Whether a `Vec` field holds every remaining item in the stream, or an `Option` field is
+    /// only present if the stream has not ended. This has to be opted into since formats rarely
+    /// end with a field which is not delimited.
+    pub fn until_eof(&self) -> bool {
+        self.until_eof.is_present()
+    }
+
+    /// The `TextFormat` used for string fields with an `encoding`.
+    pub fn text_format(&self) -> Option<TokenStream> {
+        self.text.as_ref()?;
 
-        Some(quote!{ (#func)(#name, buffer)? })
+        let encoding = self.encoding.as_deref().copied().unwrap_or(Encoding::Utf8);
+        let policy = self.encoding_errors.unwrap_or(ErrorPolicy::Error);
+        Some(quote!(::io_self::encoding::TextFormat::new(#encoding, #policy)))
     }
 
-    pub fn with_endian(&mut self, opts: &Opts) {
+    /// Whether the length prefix of an encoded string counts `bytes` or `code_units` (the default).
+    pub fn length_unit(&self) -> LengthUnit {
+        self.length_unit.unwrap_or(LengthUnit::CodeUnits)
+    }
+
+    pub fn text_layout(&self) -> &TextLayout {
+        self.text.as_ref().expect("checked by FieldOpts::validate")
+    }
+
+    /// Inherit the endianness and mode of the container. A field can only use the byte order
+    /// parameter of a container which has one.
+    pub fn with_endian(&mut self, opts: &Opts) -> darling::Result<()> {
+        if let Some(endian) = &self.endian {
+            if **endian == Endian::Generic && !opts.is_generic_endian() {
+                return Err(darling::Error::custom(
+                    "fields can only have a generic endianness when their container does",
+                )
+                .with_span(&endian.span()));
+            }
+        }
+
         if self.endian.is_none() {
-            self.endian = opts.endian.clone();
+            self.endian = opts.endian.map(|endian| SpannedValue::new(endian, Span::call_site()));
         }
+        self.mode = opts.mode;
+        Ok(())
     }
 
     pub fn length_prefix_type(&self) -> Option<Type> {
-        let prefix = TokenStream::from_str(self.length_prefix.as_ref()?)
-            .expect("Unable to tokenize field length prefix");
-        Some(syn::parse2(prefix).expect("Expected type"))
+        self.length_prefix.clone()
     }
 
     pub fn endianness(&self) -> Option<Endian> {
-        match self.endian.as_ref()?.to_ascii_lowercase().as_ref() {
-            "little" | "le" | "l" => Some(Endian::Little),
-            "big" | "be" | "b" => Some(Endian::Big),
-            x => panic!("Unknown endian format: {:?}", x),
-        }
+        self.endian.as_deref().copied()
     }
 
 
-    pub fn trait_usage(&self, read: bool) -> TokenStream {
-        match (read, self.endianness()) {
-            (false, None) => quote!(::io_self::WriteSelf),
-            (true, None) => quote!(::io_self::ReadSelf),
-            (false, Some(Endian::Little)) => quote!(
-                ::io_self::derive_util::WriteSelfEndian<::io_self::derive_util::LittleEndian>
-            ),
-            (true, Some(Endian::Little)) => {
-                quote!(::io_self::derive_util::ReadSelfEndian<::io_self::derive_util::LittleEndian>)
-            }
-            (false, Some(Endian::Big)) => {
-                quote!(::io_self::derive_util::WriteSelfEndian<::io_self::derive_util::BigEndian>)
-            }
-            (true, Some(Endian::Big)) => {
-                quote!(::io_self::derive_util::ReadSelfEndian<::io_self::derive_util::BigEndian>)
-            }
+    pub fn trait_usage(&self, read: bool) -> Approach {
+        Approach {
+            read,
+            mode: self.mode,
+            endian: self.endianness(),
         }
     }
 }
@@ -0,0 +1,115 @@
+use crate::attr::{Approach, FieldOpts, Opts, VariantOpts};
+use darling::{FromField, FromVariant};
+use syn::visit::{self, Visit};
+use syn::{parse_quote, Data, DeriveInput, Field, Generics, Ident, Path, Type, TypePath};
+
+/// Adds the bounds needed to read or write the input to the where clause of its generics. Only the
+/// type parameters which appear in a field read or written with the derived trait are required to
+/// implement it, so parameters used behind `read_fn`, `with`, `map` or within `PhantomData` are
+/// left alone. A `bound` on the container replaces every inferred bound, while a `bound` on a
+/// field replaces the bounds inferred from that field, the same way serde does.
+pub fn with_bounds(input: &DeriveInput, opts: &Opts, read: bool) -> Generics {
+    let mut generics = input.generics.clone();
+    let params = generics.type_params().map(|param| param.ident.clone()).collect::<Vec<_>>();
+    let predicates = &mut generics.make_where_clause().predicates;
+
+    if let Some(bound) = opts.bound(read) {
+        predicates.extend(bound.iter().cloned());
+        return generics;
+    }
+
+    // Each parameter must implement the trait used by the fields it appears in, which may have
+    // their own endianness
+    let mut used = Vec::new();
+    let mut require = |ty: &Type, approach: Approach| {
+        let mut visitor = ParamVisitor {
+            params: &params,
+            used: Vec::new(),
+        };
+        visitor.visit_type(ty);
+
+        for param in visitor.used {
+            if !used.contains(&(param.clone(), approach)) {
+                used.push((param, approach));
+            }
+        }
+    };
+
+    let converted = match read {
+        true => opts.conversion_from().or(opts.conversion_try_from()),
+        false => opts.conversion_into(),
+    };
+    match converted {
+        Some(ty) => require(ty, opts.trait_usage(read)),
+        None => {
+            if let Ok(tag) = opts.tag_type(&input.ident) {
+                require(tag, opts.tag_usage(read));
+            }
+
+            // Fields which fail to parse are reported when building the implementation
+            for (field, parent_opts) in fields(&input.data, opts) {
+                let Ok(mut field_opts) = FieldOpts::from_field(field) else {
+                    continue;
+                };
+                if field_opts.with_endian(&parent_opts).is_err() {
+                    continue;
+                }
+
+                match field_opts.bound(read) {
+                    Some(bound) => predicates.extend(bound.iter().cloned()),
+                    None if field_opts.uses_trait(read) => require(&field.ty, field_opts.trait_usage(read)),
+                    None => {}
+                }
+            }
+        }
+    }
+
+    for (param, approach) in used {
+        predicates.push(parse_quote!(#param: #approach));
+    }
+
+    generics
+}
+
+/// Each field along with the options it inherits, which for enums may be overridden by its variant.
+fn fields<'a>(data: &'a Data, opts: &Opts) -> Vec<(&'a Field, Opts)> {
+    match data {
+        Data::Struct(struct_data) => struct_data.fields.iter().map(|f| (f, opts.clone())).collect(),
+        Data::Enum(enum_data) => enum_data
+            .variants
+            .iter()
+            .flat_map(|variant| {
+                let variant_opts = VariantOpts::from_variant(variant)
+                    .and_then(|variant_opts| variant_opts.apply(opts))
+                    .unwrap_or_else(|_| opts.clone());
+                variant.fields.iter().map(move |f| (f, variant_opts.clone()))
+            })
+            .collect(),
+        Data::Union(_) => Vec::new(),
+    }
+}
+
+/// Finds the type parameters which appear within a type.
+struct ParamVisitor<'a> {
+    params: &'a [Ident],
+    used: Vec<Ident>,
+}
+
+impl<'ast> Visit<'ast> for ParamVisitor<'_> {
+    fn visit_type_path(&mut self, ty: &'ast TypePath) {
+        // PhantomData can be read and written regardless of its parameter
+        let phantom = ty.path.segments.last().is_some_and(|segment| segment.ident == "PhantomData");
+        if !phantom {
+            visit::visit_type_path(self, ty);
+        }
+    }
+
+    fn visit_path(&mut self, path: &'ast Path) {
+        if let Some(first) = path.segments.first() {
+            if path.leading_colon.is_none() && self.params.contains(&first.ident) {
+                self.used.push(first.ident.clone());
+            }
+        }
+        visit::visit_path(self, path);
+    }
+}
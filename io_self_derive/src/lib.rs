@@ -1,35 +1,175 @@
-use crate::attr::{Opts};
+use crate::attr::{Mode, Opts};
 use darling::{FromDeriveInput};
+use proc_macro2::TokenStream;
 use quote::{quote};
-use syn::{parse_macro_input, parse_quote, DeriveInput, GenericParam};
+use syn::{parse_macro_input, parse_quote, Data, DeriveInput, Generics, Ident, Index};
 
 mod attr;
+mod bound;
 mod read;
 mod write;
 mod util;
 
 #[proc_macro_derive(ReadSelf, attributes(io_self))]
 pub fn derive_read(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
-    let mut input = parse_macro_input!(input as DeriveInput);
-    let opts = Opts::from_derive_input(&input).expect("Wrong options");
+    let input = parse_macro_input!(input as DeriveInput);
+    proc_macro::TokenStream::from(impl_read(input, Mode::Blocking))
+}
 
-    let name = input.ident;
+#[proc_macro_derive(WriteSelf, attributes(io_self))]
+pub fn derive_write(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    proc_macro::TokenStream::from(impl_write(input, Mode::Blocking))
+}
+
+/// Derives `AsyncReadSelf` from the same attributes as `ReadSelf`, except for `read_fn`,
+/// `read_with`, `with`, `encoding`, `null_terminated`, `fixed_size`, `tag_from`, `until_eof` and
+/// `follow`, which are not supported asynchronously.
+#[proc_macro_derive(AsyncReadSelf, attributes(io_self))]
+pub fn derive_async_read(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    proc_macro::TokenStream::from(impl_read(input, Mode::Async))
+}
+
+/// Derives `AsyncWriteSelf` from the same attributes as `WriteSelf`, except for `write_fn`,
+/// `write_with`, `with`, `encoding`, `null_terminated`, `fixed_size` and `tag_from`, which are not
+/// supported asynchronously.
+#[proc_macro_derive(AsyncWriteSelf, attributes(io_self))]
+pub fn derive_async_write(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    proc_macro::TokenStream::from(impl_write(input, Mode::Async))
+}
+
+/// Adds the `__O` byte order parameter used by structs with a generic endianness.
+fn endian_generics(generics: &Generics, opts: &Opts) -> Generics {
+    let mut generics = generics.clone();
+    if opts.is_generic_endian() {
+        generics
+            .params
+            .push(parse_quote!(__O: ::io_self::derive_util::ByteOrder));
+    }
+    generics
+}
+
+/// The method of the trait being implemented, where `body` evaluates to the result.
+fn trait_method(mode: Mode, read: bool, body: TokenStream) -> TokenStream {
+    match (mode, read) {
+        (Mode::Blocking, true) => quote! {
+            fn read_from<B>(__buffer: &mut B) -> ::std::io::Result<Self>
+                where B: ::std::io::Read + ::io_self::PositionAware {
+                #body
+            }
+        },
+        (Mode::Blocking, false) => quote! {
+            fn write_to<B>(&self, __buffer: &mut B) -> ::std::io::Result<()>
+                where B: ::std::io::Write + ::io_self::PositionAware {
+                #body
+            }
+        },
+        (Mode::Async, true) => quote! {
+            async fn read_from_async<B>(__buffer: &mut B) -> ::std::io::Result<Self>
+                where B: ::io_self::derive_util::tokio::io::AsyncRead
+                    + ::io_self::PositionAware
+                    + ::std::marker::Unpin
+                    + ::std::marker::Send {
+                #body
+            }
+        },
+        (Mode::Async, false) => quote! {
+            async fn write_to_async<B>(&self, __buffer: &mut B) -> ::std::io::Result<()>
+                where B: ::io_self::derive_util::tokio::io::AsyncWrite
+                    + ::io_self::PositionAware
+                    + ::std::marker::Unpin
+                    + ::std::marker::Send {
+                #body
+            }
+        },
+    }
+}
+
+/// Implements the endian trait for any byte order by delegating to the derived impl, so the type
+/// can be used within containers and structs which have an endianness.
+fn endian_agnostic(name: &Ident, generics: &Generics, opts: &Opts, read: bool) -> TokenStream {
+    if opts.is_generic_endian() {
+        return TokenStream::new();
+    }
+
+    let mut endian_generics = generics.clone();
+    endian_generics.params.push(parse_quote!(__O));
+    let (impl_generics, _, _) = endian_generics.split_for_impl();
+    let (_, ty_generics, where_clause) = generics.split_for_impl();
 
-    let trait_bound = opts.trait_usage(true);
-    for param in &mut input.generics.params {
-        if let GenericParam::Type(ref mut type_param) = *param {
-            type_param.bounds.push(parse_quote!(#trait_bound));
+    let endian_trait = opts.endian_trait(read);
+    let implemented = opts.implemented_trait(read);
+
+    let body = match (opts.mode(), read) {
+        (Mode::Blocking, true) => quote!(<Self as #implemented>::read_from(__buffer)),
+        (Mode::Blocking, false) => quote!(<Self as #implemented>::write_to(self, __buffer)),
+        (Mode::Async, true) => quote!(<Self as #implemented>::read_from_async(__buffer).await),
+        (Mode::Async, false) => quote!(<Self as #implemented>::write_to_async(self, __buffer).await),
+    };
+    let method = trait_method(opts.mode(), read, body);
+
+    quote! {
+        impl #impl_generics #endian_trait for #name #ty_generics #where_clause {
+            #[inline(always)]
+            #method
         }
     }
+}
 
-    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+/// Implements `ReadSelfTagged` or `WriteSelfTagged` for enums with a tag, so they can be used as a
+/// field whose tag is stored in an earlier field (`tag_from`). Like the endian traits, these are
+/// implemented for any byte order unless the endianness is generic.
+fn impl_tagged(name: &Ident, generics: &Generics, data: &Data, opts: &Opts, read: bool) -> darling::Result<TokenStream> {
+    let converted = opts.conversion_from().or(opts.conversion_try_from()).or(opts.conversion_into());
+    let tag_type = match (data, opts.tag_type(name)) {
+        (Data::Enum(_), Ok(tag_type)) if opts.mode() == Mode::Blocking && converted.is_none() => tag_type,
+        _ => return Ok(TokenStream::new()),
+    };
 
-    let built = read::build_read(&name, &input.data, opts);
+    let mut tagged_generics = endian_generics(generics, opts);
+    if !opts.is_generic_endian() {
+        tagged_generics.params.push(parse_quote!(__O));
+    }
+    let (impl_generics, _, _) = tagged_generics.split_for_impl();
+    let (_, ty_generics, where_clause) = generics.split_for_impl();
+
+    if !read {
+        let write = write::build_write_variant(name, data, opts)?;
+        let tag = write::build_variant_tag(name, data)?;
+        return Ok(quote! {
+            impl #impl_generics ::io_self::derive_util::WriteSelfTagged<__O> for #name #ty_generics #where_clause {
+                type Tag = #tag_type;
+
+                #[inline(always)]
+                fn variant_tag(&self) -> Self::Tag {
+                    #tag
+                }
+
+                #[inline(always)]
+                fn write_variant<B>(&self, __buffer: &mut B) -> ::std::io::Result<()>
+                    where B: ::std::io::Write + ::io_self::PositionAware {
+                    #write;
+                    Ok(())
+                }
+            }
+        });
+    }
+
+    let mut built = read::build_read_variant(name, data, opts)?;
+    if read::uses_struct_start(data) {
+        built = quote!({
+            let __start = ::io_self::PositionAware::position(__buffer)?;
+            #built
+        });
+    }
+    Ok(quote! {
+        impl #impl_generics ::io_self::derive_util::ReadSelfTagged<__O> for #name #ty_generics #where_clause {
+            type Tag = #tag_type;
 
-    proc_macro::TokenStream::from(quote! {
-        impl #impl_generics ::io_self::ReadSelf for #name #ty_generics #where_clause {
             #[inline(always)]
-            fn read_from<B>(buffer: &mut B) -> ::std::io::Result<Self>
+            fn read_variant<B>(__tag: Self::Tag, __buffer: &mut B) -> ::std::io::Result<Self>
                 where B: ::std::io::Read + ::io_self::PositionAware {
                 Ok(#built)
             }
@@ -37,34 +177,169 @@ pub fn derive_read(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     })
 }
 
-#[proc_macro_derive(WriteSelf, attributes(io_self))]
-pub fn derive_write(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
-    let mut input = parse_macro_input!(input as DeriveInput);
-    let opts = Opts::from_derive_input(&input).expect("Wrong options");
+/// Implements the plain and endian traits for a newtype with `#[io_self(transparent)]` by
+/// delegating to its only field. Each impl only applies when the field implements the same trait,
+/// so the newtype takes the endianness of its parent. Attributes on the field would change its
+/// encoding, so they are rejected rather than ignored.
+fn impl_transparent(input: &DeriveInput, opts: &Opts, read: bool) -> darling::Result<TokenStream> {
+    let field = match &input.data {
+        Data::Struct(data) if data.fields.len() == 1 => data.fields.iter().next().unwrap(),
+        _ => {
+            return Err(darling::Error::custom("transparent requires a struct with exactly one field")
+                .with_span(&input.ident))
+        }
+    };
+    if opts.endianness().is_some() {
+        return Err(darling::Error::custom("transparent structs take the endianness of their parent")
+            .with_span(&input.ident));
+    }
+    if opts.bound(read).is_some() {
+        return Err(darling::Error::custom("transparent structs are bounded by their field's type")
+            .with_span(&input.ident));
+    }
+    if let Some(attr) = field.attrs.iter().find(|attr| attr.path().is_ident("io_self")) {
+        return Err(darling::Error::custom("the field of a transparent struct is read and written with its own traits")
+            .with_span(&attr.meta));
+    }
+
+    let name = &input.ident;
+    let ty = &field.ty;
+    let (_, ty_generics, _) = input.generics.split_for_impl();
+
+    let mut impls = TokenStream::new();
+    for (approach, endian) in [(opts.trait_usage(read), false), (opts.endian_trait(read), true)] {
+        let mut generics = input.generics.clone();
+        if endian {
+            generics.params.push(parse_quote!(__O));
+        }
+        // A higher ranked bound is never trivially false, so the impl is allowed even when the
+        // field's type is concrete and does not implement the trait
+        generics
+            .make_where_clause()
+            .predicates
+            .push(parse_quote!(for<'__a> #ty: #approach));
+        let (impl_generics, _, where_clause) = generics.split_for_impl();
+
+        let body = match (read, &field.ident) {
+            (true, Some(ident)) => {
+                let read = approach.read_call(ty);
+                quote!(Ok(#name { #ident: #read }))
+            }
+            (true, None) => {
+                let read = approach.read_call(ty);
+                quote!(Ok(#name(#read)))
+            }
+            (false, Some(ident)) => {
+                let write = approach.write_call(ty, &quote!(&self.#ident));
+                quote!(#write Ok(()))
+            }
+            (false, None) => {
+                let index = Index::from(0);
+                let write = approach.write_call(ty, &quote!(&self.#index));
+                quote!(#write Ok(()))
+            }
+        };
+        let method = trait_method(opts.mode(), read, body);
+
+        impls.extend(quote! {
+            impl #impl_generics #approach for #name #ty_generics #where_clause {
+                #[inline(always)]
+                #method
+            }
+        });
+    }
+
+    Ok(impls)
+}
+
+fn impl_read(mut input: DeriveInput, mode: Mode) -> TokenStream {
+    let opts = match Opts::from_derive_input(&input) {
+        Ok(opts) => opts.with_mode(mode),
+        Err(err) => return err.write_errors(),
+    };
+    if opts.is_transparent() {
+        return impl_transparent(&input, &opts, true).unwrap_or_else(|err| err.write_errors());
+    }
 
+    input.generics = bound::with_bounds(&input, &opts, true);
     let name = input.ident;
 
-    let trait_bound = opts.trait_usage(false);
-    for param in &mut input.generics.params {
-        if let GenericParam::Type(ref mut type_param) = *param {
-            type_param.bounds.push(parse_quote!(#trait_bound));
+    let implemented = opts.implemented_trait(true);
+    let generics = endian_generics(&input.generics, &opts);
+    let (impl_generics, _, _) = generics.split_for_impl();
+    let (_, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let agnostic = endian_agnostic(&name, &input.generics, &opts, true);
+    let tagged = match impl_tagged(&name, &input.generics, &input.data, &opts, true) {
+        Ok(tagged) => tagged,
+        Err(err) => return err.write_errors(),
+    };
+    let mut built = match read::build_read(&name, &input.data, opts) {
+        Ok(built) => built,
+        Err(err) => return err.write_errors(),
+    };
+    if read::uses_struct_start(&input.data) {
+        built = quote!({
+            let __start = ::io_self::PositionAware::position(__buffer)?;
+            #built
+        });
+    }
+
+    let method = trait_method(mode, true, quote!(Ok(#built)));
+    let implementation = quote! {
+        impl #impl_generics #implemented for #name #ty_generics #where_clause {
+            #[inline(always)]
+            #method
         }
+    };
+
+    quote! {
+        #implementation
+        #agnostic
+        #tagged
     }
+}
+
+fn impl_write(mut input: DeriveInput, mode: Mode) -> TokenStream {
+    let opts = match Opts::from_derive_input(&input) {
+        Ok(opts) => opts.with_mode(mode),
+        Err(err) => return err.write_errors(),
+    };
+    if opts.is_transparent() {
+        return impl_transparent(&input, &opts, false).unwrap_or_else(|err| err.write_errors());
+    }
+
+    input.generics = bound::with_bounds(&input, &opts, false);
+    let name = input.ident;
 
-    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let implemented = opts.implemented_trait(false);
+    let generics = endian_generics(&input.generics, &opts);
+    let (impl_generics, _, _) = generics.split_for_impl();
+    let (_, ty_generics, where_clause) = input.generics.split_for_impl();
 
-    let built = write::build_write(&name, &input.data, opts);
+    let agnostic = endian_agnostic(&name, &input.generics, &opts, false);
+    let tagged = match impl_tagged(&name, &input.generics, &input.data, &opts, false) {
+        Ok(tagged) => tagged,
+        Err(err) => return err.write_errors(),
+    };
+    let built = match write::build_write(&name, &input.data, opts) {
+        Ok(built) => built,
+        Err(err) => return err.write_errors(),
+    };
 
-    proc_macro::TokenStream::from(quote! {
-        impl #impl_generics ::io_self::WriteSelf for #name #ty_generics #where_clause {
+    let method = trait_method(mode, false, quote!(#built; Ok(())));
+    let implementation = quote! {
+        impl #impl_generics #implemented for #name #ty_generics #where_clause {
             #[inline(always)]
-            fn write_to<B>(&self, buffer: &mut B) -> ::std::io::Result<()>
-                where B: ::std::io::Write + ::io_self::PositionAware {
-                #built;
-                Ok(())
-            }
+            #method
         }
-    })
+    };
+
+    quote! {
+        #implementation
+        #agnostic
+        #tagged
+    }
 }
 
 
@@ -82,5 +357,37 @@ mod tests {
         test_cases.pass("tests/07-length-prefix.rs");
         test_cases.pass("tests/08-prefixed-vec.rs");
         test_cases.pass("tests/09-field-specific-parsers.rs");
+        test_cases.pass("tests/10-async.rs");
+        test_cases.pass("tests/11-incremental.rs");
+        test_cases.pass("tests/12-codec.rs");
+        test_cases.pass("tests/13-record-iter.rs");
+        test_cases.pass("tests/14-file-ptr.rs");
+        test_cases.pass("tests/15-pointer-writer.rs");
+        test_cases.pass("tests/16-terminated.rs");
+        test_cases.pass("tests/17-strings.rs");
+        test_cases.pass("tests/18-encoding.rs");
+        test_cases.pass("tests/19-varint.rs");
+        test_cases.pass("tests/20-bits.rs");
+        test_cases.pass("tests/21-odd-width.rs");
+        test_cases.pass("tests/22-endian-wrappers.rs");
+        test_cases.pass("tests/23-generic-endian.rs");
+        test_cases.pass("tests/24-endian-containers.rs");
+        test_cases.pass("tests/25-typed-attributes.rs");
+        test_cases.pass("tests/26-with-codec.rs");
+        test_cases.pass("tests/27-map.rs");
+        test_cases.pass("tests/28-transparent.rs");
+        test_cases.pass("tests/29-bounds.rs");
+        test_cases.pass("tests/30-untagged.rs");
+        test_cases.pass("tests/31-tag-from.rs");
+        test_cases.pass("tests/32-composite-tags.rs");
+        test_cases.pass("tests/33-variant-attributes.rs");
+        test_cases.pass("tests/34-custom-tags.rs");
+        test_cases.pass("tests/35-endian-agnostic.rs");
+    }
+
+    #[test]
+    pub fn compile_errors() {
+        let test_cases = trybuild::TestCases::new();
+        test_cases.compile_fail("tests/fail/*.rs");
     }
 }
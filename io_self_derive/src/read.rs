@@ -1,137 +1,452 @@
-use crate::attr::{FieldOpts, Opts, VariantOpts};
+use crate::attr::{Approach, FieldOpts, Mode, Opts, TextLayout, VariantOpts};
 use darling::{FromField, FromVariant};
 use proc_macro2::{self, Ident, TokenStream};
 use quote::{quote, quote_spanned};
 use syn::spanned::Spanned;
-use syn::{parse_quote, Data, Fields, Type, DataEnum};
+use syn::{parse_quote, Data, DataEnum, Field, Fields, Path, Type, Variant};
 
 use crate::util;
 
-pub fn build_read(name: &Ident, data: &Data, opts: Opts) -> TokenStream {
+pub fn build_read(name: &Ident, data: &Data, opts: Opts) -> darling::Result<TokenStream> {
+    if let Some(read) = read_converted(name, &opts)? {
+        return Ok(read);
+    }
+
+    match data {
+        Data::Struct(struct_data) => derive_read_fields(&quote!(#name), &struct_data.fields, &opts, None),
+        Data::Union(_) => Err(darling::Error::custom("unions are not supported").with_span(name)),
+        Data::Enum(enum_data) => read_enum(name, enum_data, &opts, false)
+    }
+}
+
+/// Reads the variant selected by the tag `__tag` for enums whose tag is stored outside of them.
+pub fn build_read_variant(name: &Ident, data: &Data, opts: &Opts) -> darling::Result<TokenStream> {
     match data {
-        Data::Struct(struct_data) => {
-            let fields = derive_read_fields(&struct_data.fields, &opts);
-            quote_spanned!(name.span() => #name #fields)
+        Data::Enum(enum_data) => read_enum(name, enum_data, opts, true),
+        _ => Err(darling::Error::custom("only enums can be read with an external tag").with_span(name)),
+    }
+}
+
+
+/// Reads the type given by `from` or `try_from` and converts it into `Self`, the same way serde
+/// does.
+fn read_converted(name: &Ident, opts: &Opts) -> darling::Result<Option<TokenStream>> {
+    let approach = opts.trait_usage(true);
+
+    match (opts.conversion_from(), opts.conversion_try_from()) {
+        (Some(_), Some(try_from)) => {
+            Err(darling::Error::custom("from and try_from cannot be used together").with_span(try_from))
+        }
+        (Some(from), None) => {
+            let read = approach.read_call(from);
+            Ok(Some(quote_spanned!(from.span() => <Self as ::std::convert::From<#from>>::from(#read))))
         }
-        Data::Union(_) => panic!("Unable to derive for union"),
-        Data::Enum(enum_data) => read_enum(name, enum_data, opts)
+        (None, Some(try_from)) => {
+            let read = approach.read_call(try_from);
+            let context = format!("`{}`", name);
+            Ok(Some(quote_spanned!(try_from.span() =>
+                match <Self as ::std::convert::TryFrom<#try_from>>::try_from(#read) {
+                    Ok(value) => value,
+                    Err(err) => return Err(::io_self::derive_util::invalid_conversion(#context, err)),
+                }
+            )))
+        }
+        (None, None) => Ok(None),
     }
 }
 
+/// Reads an enum, optionally from within a length prefixed buffer. The tag is read first unless it
+/// is `external`, in which case it was passed in as `__tag`.
+fn read_enum(name: &Ident, enum_data: &DataEnum, opts: &Opts, external: bool) -> darling::Result<TokenStream> {
+    let read_variant = match opts.is_untagged() {
+        true => read_untagged(name, enum_data, opts)?,
+        false => read_tagged(name, enum_data, opts, external)?,
+    };
 
-fn read_enum(name: &Ident, enum_data: &DataEnum, opts: Opts) -> TokenStream {
-    let tag_type = opts
-        .tag_type()
-        .expect("Enums must have a tag type to distinguish variants");
+    match opts.length_prefix_type() {
+        Some(prefix_type) => Ok(read_prefixed(prefix_type, opts, read_variant)),
+        None => Ok(read_variant),
+    }
+}
 
-    let tag = read_for_type(&tag_type, &opts.trait_usage(true), None);
+/// Reads the number of bytes given by a length prefix, then evaluates `body` with `__buffer`
+/// holding exactly those bytes.
+fn read_prefixed(prefix_type: &Type, opts: &Opts, body: TokenStream) -> TokenStream {
+    let read_prefix = read_for_type(prefix_type, &opts.trait_usage(true), None);
+    let read_len = util::try_from(&parse_quote!(usize), prefix_type, &read_prefix);
+    let read_body = match opts.mode() {
+        Mode::Blocking => quote!(__buffer.read_exact(&mut element_buffer)?;),
+        Mode::Async => quote! {
+            ::io_self::derive_util::tokio::io::AsyncReadExt::read_exact(__buffer, &mut element_buffer).await?;
+        },
+    };
 
-    let variants = enum_data.variants.iter().map(|variant| {
-        let tag = VariantOpts::from_variant(variant)
-            .expect("Unexpect attribute fields")
-            .tag();
+    quote_spanned! {prefix_type.span() => {
+        let mut element_buffer = vec![0u8; #read_len];
+        #read_body
+        let mut cursor = ::std::io::Cursor::new(element_buffer);
+        let __buffer = &mut cursor;
 
-        let variant_name = &variant.ident;
-        let fields = derive_read_fields(&variant.fields, &opts);
-        quote!(#tag => #name::#variant_name #fields)
+        #body
+    }}
+}
+
+/// Reads the fields of a variant along with the magic, padding and length prefix given by its own
+/// attributes. Attributes of the variant take the place of the enum's for its fields.
+fn read_variant(name: &Ident, variant: &Variant, variant_opts: &VariantOpts, opts: &Opts) -> darling::Result<TokenStream> {
+    let opts = variant_opts.apply(opts)?;
+    let variant_name = &variant.ident;
+    let context = format!("variant `{}::{}`", name, variant_name);
+
+    let check = variant_opts.validate().map(|check| {
+        let check = util::bind_self_fields(check);
+        let message = format!("{} failed validation", context);
+        quote_spanned!(check.span() => match #check {
+            true => {}
+            false => return Err(::std::io::Error::new(::std::io::ErrorKind::InvalidData, #message)),
+        })
     });
+    let fields = derive_read_fields(&quote!(#name::#variant_name), &variant.fields, &opts, check)?;
 
-    if let Some(prefix_type) = opts.length_prefix_type() {
-        let read_prefix = read_for_type(&prefix_type, &opts.trait_usage(true), None);
-        let read_len = util::try_from(&parse_quote!(usize), &prefix_type, &read_prefix);
+    let magic = variant_opts.magic().map(|magic| match opts.mode() {
+        Mode::Blocking => quote_spanned!(magic.span() =>
+            ::io_self::derive_util::read_magic(__buffer, ::std::convert::AsRef::<[u8]>::as_ref(&#magic), #context)?;
+        ),
+        Mode::Async => quote_spanned!(magic.span() =>
+            ::io_self::derive_util::read_magic_async(__buffer, ::std::convert::AsRef::<[u8]>::as_ref(&#magic), #context).await?;
+        ),
+    });
+    let pad = variant_opts.pad_after().map(|len| match opts.mode() {
+        Mode::Blocking => quote!(::io_self::grammar::consume_bytes(__buffer, #len)?;),
+        Mode::Async => quote!(::io_self::grammar::consume_bytes_async(__buffer, #len).await?;),
+    });
 
-        quote_spanned! {name.span() => {
-            let mut element_buffer = vec![0u8; #read_len];
-            buffer.read_exact(&mut element_buffer)?;
-            let mut cursor = ::std::io::Cursor::new(element_buffer);
-            let buffer = &mut cursor;
+    let body = match (&magic, &pad) {
+        (None, None) => fields,
+        _ => quote!({
+            #magic
+            let __variant = #fields;
+            #pad
+            __variant
+        }),
+    };
 
-            match #tag {
-                #(#variants,)*
-                x => return Err(::std::io::Error::new(::std::io::ErrorKind::InvalidData, format!("Invalid tag value: {:?}", x))),
-            }
-        }}
-    } else {
-        quote_spanned!(name.span() =>
-            match #tag {
-                #(#variants,)*
-                x => return Err(::std::io::Error::new(::std::io::ErrorKind::InvalidData, format!("Invalid tag value: {:?}", x))),
-            }
-        )
+    match variant_opts.length_prefix_type() {
+        Some(prefix_type) => Ok(read_prefixed(prefix_type, &opts, body)),
+        None => Ok(body),
     }
 }
 
-pub fn read_for_type(name: &Type, approach: &TokenStream, prefix_length: Option<Type>) -> TokenStream {
+/// Reads the tag and then the fields of the variant it selects.
+fn read_tagged(name: &Ident, enum_data: &DataEnum, opts: &Opts, external: bool) -> darling::Result<TokenStream> {
+    let tag_type = opts.tag_type(name)?;
+
+    let read_tag = match external {
+        true => quote!(__tag),
+        false => read_for_type(tag_type, &opts.tag_usage(true), None),
+    };
+    let tag = quote!(::io_self::TagValue::into_value(#read_tag));
+
+    let mut errors = darling::Error::accumulator();
+    let variants = enum_data.variants.iter().filter_map(|variant| {
+        let variant_opts = errors.handle(VariantOpts::from_variant(variant))?;
+        let tag = errors.handle(variant_opts.tag())?;
+
+        let fields = errors.handle(read_variant(name, variant, &variant_opts, opts))?;
+        Some(quote!(#tag => #fields))
+    }).collect::<Vec<_>>();
+    errors.finish()?;
+
+    Ok(quote_spanned!(name.span() =>
+        match #tag {
+            #(#variants,)*
+            // Custom tag types may already be covered by the variants
+            #[allow(unreachable_patterns)]
+            x => return Err(::std::io::Error::new(::std::io::ErrorKind::InvalidData, format!("Invalid tag value: {:?}", x))),
+        }
+    ))
+}
+
+/// Tries to read each variant in order, seeking back to where the enum started after each failure.
+/// If none of the variants can be read, the error lists what went wrong with each of them.
+fn read_untagged(name: &Ident, enum_data: &DataEnum, opts: &Opts) -> darling::Result<TokenStream> {
+    let enum_label = name.to_string();
+    let mut errors = darling::Error::accumulator();
+    let variants = enum_data.variants.iter().filter_map(|variant| {
+        let variant_opts = errors.handle(VariantOpts::from_variant(variant))?;
+        errors.handle(variant_opts.check_untagged())?;
+
+        let variant_name = &variant.ident;
+        let fields = errors.handle(read_variant(name, variant, &variant_opts, opts))?;
+        let attempt = match opts.mode() {
+            Mode::Blocking => quote!(::io_self::derive_util::read_attempt(|| Ok(#fields))),
+            Mode::Async => quote!(async { Ok::<_, ::std::io::Error>(#fields) }.await),
+        };
+
+        let variant_label = variant_name.to_string();
+        Some(quote! {
+            match #attempt {
+                Ok(value) => break '__untagged value,
+                Err(err) => {
+                    __failures.push((#variant_label, err));
+                    ::io_self::derive_util::rewind_untagged(__buffer, __variant_start, #enum_label, &__failures)?;
+                }
+            }
+        })
+    }).collect::<Vec<_>>();
+    errors.finish()?;
+
+    Ok(quote_spanned!(name.span() =>
+        '__untagged: {
+            let __variant_start = ::io_self::PositionAware::position(__buffer)?;
+            let mut __failures = ::std::vec::Vec::new();
+            #(#variants)*
+            return Err(::io_self::derive_util::untagged_error(#enum_label, __failures));
+        }
+    ))
+}
+
+pub fn read_for_type(name: &Type, approach: &Approach, prefix_length: Option<Type>) -> TokenStream {
     if let Some(prefix) = prefix_length {
         let read_len = read_for_type(&prefix, approach, None);
         let item_count = util::try_from(&parse_quote!(usize), &prefix, &quote!(raw_len));
 
-        return quote_spanned!(name.span() => {
-            let raw_len = #read_len;
-            let length = #item_count;
+        let endian = approach.endian_type();
+        if util::is_string(name) {
+            let read_string = match approach.mode() {
+                Mode::Blocking => quote!(::io_self::derive_util::read_string_with_length(__buffer, length)?),
+                Mode::Async => quote!(::io_self::derive_util::read_string_with_length_async(__buffer, length).await?),
+            };
 
-            ::io_self::derive_util::read_with_length(buffer, length, <_ as #approach>::read_from)?
-        });
+            return quote_spanned!(name.span() => {
+                let raw_len = #read_len;
+                let length = #item_count;
+
+                #read_string
+            });
+        }
+
+        return match approach.mode() {
+            Mode::Blocking => quote_spanned!(name.span() => {
+                let raw_len = #read_len;
+                let length = #item_count;
+
+                ::io_self::derive_util::read_with_length(__buffer, length, <_ as #approach>::read_from)?
+            }),
+            Mode::Async => quote_spanned!(name.span() => {
+                let raw_len = #read_len;
+                let length = #item_count;
+
+                ::io_self::derive_util::read_with_length_async::<#endian, _, _, #name>(__buffer, length).await?
+            }),
+        };
     }
 
-    match name {
-        Type::Array(arr) => {
-            let arr_type = &*arr.elem;
-            let arr_len = &arr.len;
-            let read_element = read_for_type(arr_type, approach, None);
-            quote_spanned! {
-                name.span() =>
-                unsafe {
-                    use std::mem::MaybeUninit;
-                    let mut array = MaybeUninit::<[MaybeUninit<#arr_type>; #arr_len]>::uninit().assume_init();
+    approach.read_call(name)
+}
 
-                    for item in array.iter_mut().take(#arr_len) {
-                        item.write(#read_element);
-                    }
+/// Reads each field into a local variable before building `path` from them. This lets attributes
+/// on later fields refer to the values of fields which have already been read, as can `check`,
+/// which runs once all of the fields have been read.
+pub fn derive_read_fields(
+    path: &TokenStream,
+    data_fields: &Fields,
+    opts: &Opts,
+    check: Option<TokenStream>,
+) -> darling::Result<TokenStream> {
+    let all_opts = util::field_opts(data_fields, opts)?;
+    let bit_groups = util::bit_groups(&all_opts);
+    let tag_sources = util::tag_sources(data_fields, &all_opts)?;
+    let fields = data_fields.iter().collect::<Vec<_>>();
 
-                    (&array as *const _ as *const #arr).read()
-                }
+    let mut locals = Vec::new();
+    let reads = data_fields.iter().zip(&all_opts).zip(bit_groups).enumerate().map(|(idx, ((f, field_opts), group))| {
+        let local = util::field_local(f, idx);
+        locals.push(local.clone());
+
+        // The type on the wire is inferred from `map` or `try_map`
+        let ty = match field_opts.is_mapped() {
+            true => parse_quote!(_),
+            false => f.ty.clone(),
+        };
+        let tag = tag_sources[idx].map(|source| util::field_local(fields[source], source));
+        let formula = match (tag, field_opts.read_fn(), field_opts.codec(), field_opts.text_format(), field_opts.bits()) {
+            (Some(tag), _, _, _, _) => read_external(&ty, &tag, field_opts),
+            (None, Some(v), _, _, _) => v,
+            (None, None, Some(codec), _, _) => read_codec(&ty, codec, field_opts),
+            (None, None, None, Some(format), _) => read_text(&ty, field_opts, &format),
+            (None, None, None, None, Some(bits)) => read_bits(&ty, bits, field_opts.mode()),
+            (None, None, None, None, None) if field_opts.until_eof() => read_until_eof(&ty, field_opts),
+            (None, None, None, None, None) if util::is_string(&ty) && field_opts.length_prefix_type().is_none() => {
+                quote_spanned!(ty.span() => compile_error!("String fields require one of length_prefix, null_terminated or fixed_size"))
             }
+            (None, None, None, None, None) if util::is_named(&ty, "Vec") && field_opts.length_prefix_type().is_none() => {
+                quote_spanned!(ty.span() => compile_error!("Vec fields require a length_prefix, or until_eof to read the rest of the stream"))
+            }
+            (None, None, None, None, None) if util::is_named(&ty, "Option") => {
+                quote_spanned!(ty.span() => compile_error!("Option fields require until_eof to be absent at the end of the stream"))
+            }
+            (None, None, None, None, None) => read_for_type(&ty, &field_opts.trait_usage(true), field_opts.length_prefix_type()),
+        };
+        let formula = field_opts.map_read(formula, &util::field_context(f, idx));
+        let start_group = group.start.then(|| {
+            let order = opts.bit_order();
+            quote!(let mut __bits = ::io_self::bits::BitReader::new(#order);)
+        });
+        let end_group = group.end.map(|total| {
+            util::check_bit_group(total, f.span()).unwrap_or_else(|| quote!(__bits.finish()?;))
+        });
+
+        let read = match read_pointer(&local, field_opts) {
+            Some(pointer) => quote_spanned!(f.span() => let mut #local = #formula; #pointer),
+            None => quote_spanned!(f.span() => let #local = #formula;),
+        };
+        quote_spanned!(f.span() => #start_group #read #end_group)
+    }).collect::<Vec<_>>();
+
+    let construct = match data_fields {
+        Fields::Named(_) => quote!(#path { #(#locals,)* }),
+        Fields::Unnamed(_) => quote!(#path ( #(#locals,)* )),
+        Fields::Unit => quote!(#path),
+    };
+
+    if reads.is_empty() && check.is_none() {
+        return Ok(construct);
+    }
+
+    Ok(quote_spanned!(data_fields.span() => {
+        #(#reads)*
+        #check
+        #construct
+    }))
+}
+
+/// Reads an enum field whose tag was read into the earlier field `tag` given by `tag_from`.
+fn read_external(ty: &Type, tag: &TokenStream, field_opts: &FieldOpts) -> TokenStream {
+    if field_opts.mode() == Mode::Async {
+        return quote_spanned!(ty.span() => compile_error!("tag_from is not supported by AsyncReadSelf"));
+    }
+
+    let endian = field_opts.trait_usage(true).endian_type();
+    quote_spanned!(ty.span() =>
+        <#ty as ::io_self::derive_util::ReadSelfTagged<#endian>>::read_variant(
+            ::std::clone::Clone::clone(&#tag),
+            __buffer,
+        )?
+    )
+}
+
+/// Reads a field from the next `bits` bits of the current bit group.
+fn read_bits(ty: &Type, bits: u32, mode: Mode) -> TokenStream {
+    if let Some(error) = util::check_bit_width(ty, bits) {
+        return error;
+    }
+
+    match mode {
+        Mode::Blocking => quote_spanned!(ty.span() => __bits.read::<#ty, _>(__buffer, #bits)?),
+        Mode::Async => quote_spanned!(ty.span() => __bits.read_async::<#ty, _>(__buffer, #bits).await?),
+    }
+}
+
+/// Reads a `Vec` or `Option` field marked `until_eof`, which ends with the stream.
+fn read_until_eof(ty: &Type, field_opts: &FieldOpts) -> TokenStream {
+    if field_opts.mode() == Mode::Async {
+        return quote_spanned!(ty.span() => compile_error!("until_eof is not supported by AsyncReadSelf"));
+    }
+
+    let approach = field_opts.trait_usage(true);
+    quote_spanned!(ty.span() =>
+        <#ty as ::io_self::derive_util::ReadUntilEof<_>>::read_until_eof(__buffer, |__tracker| {
+            <_ as #approach>::read_from(__tracker)
+        })?
+    )
+}
+
+/// Reads a field with the `read_from` function of a `with` codec. When the field has a length
+/// prefix, the codec reads from a buffer holding exactly that many bytes.
+fn read_codec(ty: &Type, codec: &Path, field_opts: &FieldOpts) -> TokenStream {
+    if field_opts.mode() == Mode::Async {
+        return quote_spanned!(ty.span() => compile_error!("with is not supported by AsyncReadSelf"));
+    }
+
+    let approach = field_opts.trait_usage(true);
+    let endian = approach.endian_type();
+    let args = field_opts.codec_args(true);
+
+    match field_opts.length_prefix_type() {
+        Some(prefix) => {
+            let read_len = read_for_type(&prefix, &approach, None);
+            let length = util::try_from(&parse_quote!(usize), &prefix, &quote!(raw_len));
+
+            quote_spanned!(codec.span() => {
+                let raw_len = #read_len;
+                let mut element_buffer = vec![0u8; #length];
+                __buffer.read_exact(&mut element_buffer)?;
+                #codec::read_from::<#endian, _>(&mut ::std::io::Cursor::new(element_buffer), #args)?
+            })
         }
-        Type::Tuple(tuple) => {
-            let fields = tuple.elems.iter().map(|f| read_for_type(f, approach, None));
-            quote_spanned!(name.span() => ( #(#fields,)*) )
-        }
-        x => quote_spanned! {x.span() => <#x as #approach>::read_from(buffer)? },
-    }
-}
-
-pub fn derive_read_fields(data_fields: &Fields, opts: &Opts) -> TokenStream {
-    match data_fields {
-        Fields::Named(fields) => {
-            let assigned_fields = fields.named.iter().map(|f| {
-                let mut field_opts = FieldOpts::from_field(f).expect("Unexpect attribute fields");
-                field_opts.with_endian(opts);
-                let name = &f.ident;
-                let formula = match field_opts.read_fn() {
-                    Some(v) => v,
-                    None => read_for_type(&f.ty, &field_opts.trait_usage(true), field_opts.length_prefix_type()),
-                };
-                quote_spanned!(f.span() => #name: #formula)
-            });
+        None => quote_spanned!(codec.span() => #codec::read_from::<#endian, _>(__buffer, #args)?),
+    }
+}
 
-            quote_spanned!(data_fields.span() => { #(#assigned_fields,)* })
+/// Reads a string field stored with an `encoding`.
+fn read_text(ty: &Type, field_opts: &FieldOpts, format: &TokenStream) -> TokenStream {
+    if field_opts.mode() == Mode::Async {
+        return quote_spanned!(ty.span() => compile_error!("encoding is not supported by AsyncReadSelf"));
+    }
+
+    match field_opts.text_layout() {
+        TextLayout::Prefixed(prefix) => {
+            let read_len = read_for_type(prefix, &field_opts.trait_usage(true), None);
+            let length = util::try_from(&parse_quote!(usize), prefix, &quote!(raw_len));
+            let unit = field_opts.length_unit();
+
+            quote_spanned!(ty.span() => {
+                let raw_len = #read_len;
+                #format.read_with_length(__buffer, #length, #unit)?
+            })
         }
-        Fields::Unnamed(fields) => {
-            let assigned_fields = fields
-                .unnamed
-                .iter()
-                .map(|f| {
-                    let mut field_opts = FieldOpts::from_field(f).expect("Unexpect attribute fields");
-                    field_opts.with_endian(opts);
-
-                    match field_opts.read_fn() {
-                        Some(v) => v,
-                        None => read_for_type(&f.ty, &field_opts.trait_usage(true), field_opts.length_prefix_type()),
-                    }
-                });
-            quote_spanned!(data_fields.span() => ( #(#assigned_fields,)*) )
+        TextLayout::Terminated(max_len) => {
+            // Unterminated input is bounded the same way as a `CString` unless a limit is given
+            let max_len = match max_len {
+                Some(max_len) => quote!(#max_len),
+                None => quote!(::io_self::grammar::CSTRING_MAX_LEN),
+            };
+            quote_spanned!(ty.span() => #format.read_terminated(__buffer, #max_len)?)
         }
-        Fields::Unit => quote_spanned!(data_fields.span() => ),
+        TextLayout::Fixed(size) => quote_spanned!(ty.span() => #format.read_fixed(__buffer, #size)?),
     }
 }
+
+/// Sets the base and optionally resolves the target of a `FilePtr` field after it has been read.
+fn read_pointer(local: &TokenStream, field_opts: &FieldOpts) -> Option<TokenStream> {
+    let set_base = field_opts.offset_base().map(|base| quote!(#local.set_base(#base);));
+
+    let follow = match (field_opts.follow(), field_opts.mode()) {
+        (false, _) => None,
+        (true, Mode::Blocking) => match field_opts.endianness() {
+            Some(endian) => Some(quote!(#local.resolve_endian::<#endian, _>(__buffer)?;)),
+            None => Some(quote!(#local.resolve(__buffer)?;)),
+        },
+        (true, Mode::Async) => Some(quote!(compile_error!("follow is not supported by AsyncReadSelf");)),
+    };
+
+    if set_base.is_none() && follow.is_none() {
+        return None;
+    }
+
+    Some(quote!(#set_base #follow))
+}
+
+/// Returns true if any field requires the position at the start of the struct.
+pub fn uses_struct_start(data: &Data) -> bool {
+    let fields: Box<dyn Iterator<Item = &Field>> = match data {
+        Data::Struct(struct_data) => Box::new(struct_data.fields.iter()),
+        Data::Enum(enum_data) => Box::new(enum_data.variants.iter().flat_map(|v| v.fields.iter())),
+        Data::Union(_) => return false,
+    };
+
+    fields
+        .filter_map(|f| FieldOpts::from_field(f).ok())
+        .any(|field_opts| field_opts.uses_struct_start())
+}
@@ -1,6 +1,10 @@
-use proc_macro2::{self, TokenStream};
-use quote::quote;
-use syn::Type;
+use proc_macro2::{self, Span, TokenStream};
+use crate::attr::{FieldOpts, Opts};
+use darling::FromField;
+use quote::{format_ident, quote, quote_spanned};
+use syn::spanned::Spanned;
+use syn::visit_mut::{self, VisitMut};
+use syn::{Expr, Field, Fields, Member, Type};
 
 
 pub fn try_from(ty: &Type, from_ty: &Type, expr: &TokenStream) -> TokenStream {
@@ -12,3 +16,192 @@ pub fn try_from(ty: &Type, from_ty: &Type, expr: &TokenStream) -> TokenStream {
     }
 }
 
+
+/// The local variable a field is bound to while it is read or written. Unnamed fields use their
+/// index (Ex: `__field0`).
+pub fn field_local(field: &Field, idx: usize) -> TokenStream {
+    match &field.ident {
+        Some(ident) => quote!(#ident),
+        None => member_local(&Member::Unnamed(idx.into())),
+    }
+}
+
+fn member_local(member: &Member) -> TokenStream {
+    match member {
+        Member::Named(ident) => quote!(#ident),
+        Member::Unnamed(index) => {
+            let ident = format_ident!("__field{}", index.index, span = index.span);
+            quote!(#ident)
+        }
+    }
+}
+
+/// Replaces each `self.field` within an expression with the local the field is bound to, so
+/// fields of tuple structs and variants can be referred to as `self.0`.
+pub fn bind_self_fields(expr: &Expr) -> Expr {
+    let mut expr = expr.clone();
+    SelfFields.visit_expr_mut(&mut expr);
+    expr
+}
+
+struct SelfFields;
+
+impl VisitMut for SelfFields {
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        if let Expr::Field(field) = expr {
+            if matches!(&*field.base, Expr::Path(base) if base.path.is_ident("self")) {
+                let local = member_local(&field.member);
+                *expr = Expr::Verbatim(local);
+                return;
+            }
+        }
+        visit_mut::visit_expr_mut(self, expr);
+    }
+}
+
+
+/// How a field is referred to in error messages (Ex: "field `length`" or "field 0").
+pub fn field_context(field: &Field, idx: usize) -> String {
+    match &field.ident {
+        Some(ident) => format!("field `{}`", ident),
+        None => format!("field {}", idx),
+    }
+}
+
+
+/// Parses the options of every field, reporting the errors for all of them at once.
+pub fn field_opts(data_fields: &Fields, opts: &Opts) -> darling::Result<Vec<FieldOpts>> {
+    let mut errors = darling::Error::accumulator();
+    let all_opts = data_fields
+        .iter()
+        .filter_map(|f| {
+            let field_opts = FieldOpts::from_field(f).and_then(|mut field_opts| {
+                field_opts.with_endian(opts)?;
+                Ok(field_opts)
+            });
+            errors.handle(field_opts.map_err(|err| err.with_span(&f.ty)))
+        })
+        .collect();
+
+    errors.finish_with(all_opts)
+}
+
+
+/// The index of the field named by the `tag_from` of each field. The tag must be read before the
+/// enum which uses it, so it has to be one of the earlier fields.
+pub fn tag_sources(data_fields: &Fields, all_opts: &[FieldOpts]) -> darling::Result<Vec<Option<usize>>> {
+    let members = data_fields.iter().enumerate().map(|(idx, f)| match &f.ident {
+        Some(ident) => Member::Named(ident.clone()),
+        None => Member::Unnamed(idx.into()),
+    }).collect::<Vec<_>>();
+
+    let mut errors = darling::Error::accumulator();
+    let sources = all_opts.iter().enumerate().map(|(idx, field_opts)| {
+        let tag = field_opts.tag_from()?;
+        let source = members[..idx].iter().position(|member| member == tag);
+        if source.is_none() {
+            errors.push(darling::Error::custom("tag_from must name an earlier field").with_span(tag));
+        }
+        source
+    }).collect();
+
+    errors.finish_with(sources)
+}
+
+
+/// Strings are length prefixed by their length in bytes rather than by a number of items, so they
+/// need to be handled separately. This can only check the name of the type, so aliases of `String`
+/// are not detected.
+pub fn is_string(ty: &Type) -> bool {
+    match ty {
+        Type::Path(path) if path.qself.is_none() => path
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "String" && segment.arguments.is_empty()),
+        _ => false,
+    }
+}
+
+/// Whether the type is named `name`, ignoring any generic arguments. Like `is_string`, this only
+/// checks the name of the type.
+pub fn is_named(ty: &Type, name: &str) -> bool {
+    match ty {
+        Type::Path(path) if path.qself.is_none() => path
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == name),
+        _ => false,
+    }
+}
+
+/// Where a field sits within a run of consecutive `bits` fields.
+#[derive(Default, Copy, Clone)]
+pub struct BitGroup {
+    /// The field is the first in its group.
+    pub start: bool,
+    /// The field is the last in its group, along with the total number of bits in the group.
+    pub end: Option<u32>,
+}
+
+pub fn bit_groups(fields: &[FieldOpts]) -> Vec<BitGroup> {
+    let mut groups = vec![BitGroup::default(); fields.len()];
+    let mut total = 0;
+
+    for (idx, field) in fields.iter().enumerate() {
+        let Some(bits) = field.bits() else {
+            continue;
+        };
+
+        groups[idx].start = total == 0;
+        total += bits;
+
+        if fields.get(idx + 1).and_then(FieldOpts::bits).is_none() {
+            groups[idx].end = Some(total);
+            total = 0;
+        }
+    }
+
+    groups
+}
+
+/// The number of bits in a primitive which can be used as a bit field. This can only check the
+/// name of the type, so other types are left to fail when the field is read or written.
+fn primitive_bits(ty: &Type) -> Option<u32> {
+    let Type::Path(path) = ty else {
+        return None;
+    };
+    let ident = path.path.get_ident()?.to_string();
+
+    Some(match ident.as_str() {
+        "bool" => 1,
+        "u8" | "i8" => 8,
+        "u16" | "i16" => 16,
+        "u32" | "i32" => 32,
+        "u64" | "i64" => 64,
+        _ => return None,
+    })
+}
+
+/// A field can not be wider than the primitive it is stored in.
+pub fn check_bit_width(ty: &Type, bits: u32) -> Option<TokenStream> {
+    let max = primitive_bits(ty)?;
+    if bits <= max {
+        return None;
+    }
+
+    let message = format!("a field of {} bits does not fit in {} bits", bits, max);
+    Some(quote_spanned!(ty.span() => compile_error!(#message)))
+}
+
+/// Only groups which fill a whole number of bytes are allowed, so a mistake in the field widths is
+/// caught at compile time.
+pub fn check_bit_group(total: u32, span: Span) -> Option<TokenStream> {
+    if total.is_multiple_of(8) {
+        return None;
+    }
+
+    let message = format!("bit fields total {} bits, which does not end on a byte boundary", total);
+    Some(quote_spanned!(span => compile_error!(#message);))
+}
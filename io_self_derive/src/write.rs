@@ -1,105 +1,294 @@
-use crate::attr::{FieldOpts, Opts, VariantOpts};
-use darling::{FromField, FromVariant};
+use crate::attr::{Approach, FieldOpts, Mode, Opts, TextLayout, VariantOpts};
+use darling::FromVariant;
 use proc_macro2::{self, Ident, TokenStream};
 use quote::{quote, quote_spanned};
-use std::str::FromStr;
 use syn::spanned::Spanned;
-use syn::{parse_quote, Data, Fields, Index, Type};
+use syn::{parse_quote, Data, DataEnum, Fields, Path, Type, Variant};
 
 use crate::util;
 
-const TUPLE_NAME_PLACEHOLDER: &[&str] = &[
-    "a", "b", "c", "d", "e", "f", "g", "h", "i", "j", "k", "l", "m", "n", "o", "p", "q", "r", "s",
-    "t", "u", "v", "w", "x", "y", "z",
-];
-
 
+/// Fields are bound to the same local names used when reading, so attribute expressions can refer
+/// to other fields by name in both directions.
+pub fn build_write(name: &Ident, data: &Data, opts: Opts) -> darling::Result<TokenStream> {
+    // Like serde, the conversion consumes the value so a clone is written instead
+    if let Some(into) = opts.conversion_into() {
+        let write = opts.trait_usage(false).write_call(into, &quote!(&__value));
+        return Ok(quote_spanned!(into.span() =>
+            let __value: #into = ::std::convert::Into::into(::std::clone::Clone::clone(self));
+            #write
+        ));
+    }
 
-pub fn build_write(name: &Ident, data: &Data, opts: Opts) -> TokenStream {
     match data {
         Data::Struct(struct_data) => {
-            let parent = quote!(&self);
-            derive_write_fields(&struct_data.fields, &parent, &opts, false)
+            let field_match = derive_field_match(&struct_data.fields);
+            let fields = derive_write_fields(&struct_data.fields, &opts)?;
+            Ok(quote_spanned!(name.span() => let #name #field_match = self; #fields))
         }
-        Data::Union(_) => panic!("Unable to derive for union"),
-        Data::Enum(enum_data) => {
-            let tag_type = opts
-                .tag_type()
-                .expect("Enums must have a tag type to distinguish variants");
-
-            let variants = enum_data.variants.iter().map(|variant| {
-                let tag = VariantOpts::from_variant(variant)
-                    .expect("Unexpect attribute fields")
-                    .tag();
-                let write_tag = write_for_type(&tag_type, &quote!(&variant_tag), &opts.trait_usage(false), None);
-
-                let variant_name = &variant.ident;
-                let variant_match = derive_field_match(&variant.fields);
-                let fields = derive_write_fields(&variant.fields, &quote!(), &opts, true);
+        Data::Union(_) => Err(darling::Error::custom("unions are not supported").with_span(name)),
+        Data::Enum(enum_data) => write_enum(name, enum_data, &opts, false),
+    }
+}
+
+/// Writes the active variant of an enum with its tag stored elsewhere, without writing the tag.
+pub fn build_write_variant(name: &Ident, data: &Data, opts: &Opts) -> darling::Result<TokenStream> {
+    match data {
+        Data::Enum(enum_data) => write_enum(name, enum_data, opts, true),
+        _ => Err(darling::Error::custom("only enums can be written with an external tag").with_span(name)),
+    }
+}
+
+/// The value of the tag of the active variant, for enums with their tag stored elsewhere.
+pub fn build_variant_tag(name: &Ident, data: &Data) -> darling::Result<TokenStream> {
+    let enum_data = match data {
+        Data::Enum(enum_data) => enum_data,
+        _ => return Err(darling::Error::custom("only enums have a tag").with_span(name)),
+    };
+
+    let mut errors = darling::Error::accumulator();
+    let variants = enum_data.variants.iter().filter_map(|variant| {
+        let variant_opts = errors.handle(VariantOpts::from_variant(variant))?;
+        let tag = errors.handle(variant_opts.tag())?;
+
+        let variant_name = &variant.ident;
+        Some(quote!(#name::#variant_name { .. } => ::io_self::TagValue::from_value(#tag)))
+    }).collect::<Vec<_>>();
+    errors.finish()?;
+
+    Ok(quote!(match self { #(#variants,)* }))
+}
+
+/// Writes the fields of the active variant, preceded by its tag unless the enum is untagged or the
+/// tag is `external`. Enums with a length prefix are written to a buffer first to find the length.
+fn write_enum(name: &Ident, enum_data: &DataEnum, opts: &Opts, external: bool) -> darling::Result<TokenStream> {
+    let tag_type = match opts.is_untagged() || external {
+        true => None,
+        false => Some(opts.tag_type(name)?),
+    };
+
+    let mut errors = darling::Error::accumulator();
+    let variants = enum_data.variants.iter().filter_map(|variant| {
+        let variant_opts = errors.handle(VariantOpts::from_variant(variant))?;
+        let write_tag = match tag_type {
+            Some(tag_type) => {
+                let tag = errors.handle(variant_opts.tag())?;
+                let write_tag = write_for_type(tag_type, &quote!(&variant_tag), &opts.tag_usage(false), None);
                 quote! {
-                    #name::#variant_name #variant_match => {
-                        let variant_tag: #tag_type = #tag;
-                        #write_tag
-                        #fields
-                    }
+                    let variant_tag: #tag_type = ::io_self::TagValue::from_value(#tag);
+                    #write_tag
                 }
-            });
-
-            if let Some(prefix_type) = opts.length_prefix_type() {
-                let body_len = util::try_from(
-                    &prefix_type,
-                    &parse_quote!(usize),
-                    &quote!(obj_buffer.len()),
-                );
-                let write_prefix = write_for_type(&prefix_type, &quote!(&#body_len), &opts.trait_usage(false), None);
-
-                quote_spanned! { name.span() =>
-                    let mut obj_buffer = Vec::new();
-                    { // Use temporary scope to re-use buffer ident
-                        let mut seekable_buffer = ::std::io::Cursor::new(&mut obj_buffer);
-                        let buffer = &mut seekable_buffer;
-                        match self { #(#variants,)* }
-                    }
+            }
+            None if opts.is_untagged() => {
+                errors.handle(variant_opts.check_untagged())?;
+                TokenStream::new()
+            }
+            None => TokenStream::new(),
+        };
 
-                    #write_prefix
-                    buffer.write_all(&obj_buffer[..])?;
-                }
-            } else {
-                quote_spanned!(name.span() => match self { #(#variants,)* })
+        let variant_name = &variant.ident;
+        let variant_match = derive_field_match(&variant.fields);
+        let fields = errors.handle(write_variant(variant, &variant_opts, opts))?;
+        Some(quote! {
+            #name::#variant_name #variant_match => {
+                #write_tag
+                #fields
+            }
+        })
+    }).collect::<Vec<_>>();
+    errors.finish()?;
+
+    let write_variants = quote_spanned!(name.span() => match self { #(#variants,)* });
+    match opts.length_prefix_type() {
+        Some(prefix_type) => Ok(write_prefixed(prefix_type, opts, write_variants)),
+        None => Ok(write_variants),
+    }
+}
+
+/// Writes `body` to a buffer to find its length, then writes the length prefix followed by the
+/// buffered bytes. Any pointer targets deferred within the body are forwarded to the outer stream.
+fn write_prefixed(prefix_type: &Type, opts: &Opts, body: TokenStream) -> TokenStream {
+    let body_len = util::try_from(
+        prefix_type,
+        &parse_quote!(usize),
+        &quote!(obj_buffer.len()),
+    );
+    let write_prefix = write_for_type(prefix_type, &quote!(&#body_len), &opts.trait_usage(false), None);
+
+    match opts.mode() {
+        Mode::Blocking => quote_spanned! { prefix_type.span() =>
+            let mut seekable_buffer = ::io_self::pointer::PointerWriter::new(::std::io::Cursor::new(Vec::new()));
+            { // Use temporary scope to re-use buffer ident
+                let __buffer = &mut seekable_buffer;
+                #body
             }
+            let (obj_buffer, deferred) = seekable_buffer.into_section();
+
+            #write_prefix
+            deferred.forward_to(__buffer)?;
+            __buffer.write_all(&obj_buffer[..])?;
+        },
+        Mode::Async => quote_spanned! { prefix_type.span() =>
+            let mut obj_buffer = Vec::new();
+            { // Use temporary scope to re-use buffer ident
+                let mut seekable_buffer = ::std::io::Cursor::new(&mut obj_buffer);
+                let __buffer = &mut seekable_buffer;
+                #body
+            }
+
+            #write_prefix
+            ::io_self::derive_util::tokio::io::AsyncWriteExt::write_all(__buffer, &obj_buffer[..]).await?;
+        },
+    }
+}
+
+/// Writes the fields of a variant along with the magic, padding and length prefix given by its own
+/// attributes. Attributes of the variant take the place of the enum's for its fields.
+fn write_variant(variant: &Variant, variant_opts: &VariantOpts, opts: &Opts) -> darling::Result<TokenStream> {
+    let opts = variant_opts.apply(opts)?;
+    let fields = derive_write_fields(&variant.fields, &opts)?;
+
+    let magic = variant_opts.magic().map(|magic| {
+        let magic = quote_spanned!(magic.span() => ::std::convert::AsRef::<[u8]>::as_ref(&#magic));
+        match opts.mode() {
+            Mode::Blocking => quote!(__buffer.write_all(#magic)?;),
+            Mode::Async => quote!(::io_self::derive_util::tokio::io::AsyncWriteExt::write_all(__buffer, #magic).await?;),
         }
+    });
+    let pad = variant_opts.pad_after().map(|len| match opts.mode() {
+        Mode::Blocking => quote!(::io_self::grammar::write_padding(__buffer, 0, #len)?;),
+        Mode::Async => quote!(::io_self::grammar::write_padding_async(__buffer, 0, #len).await?;),
+    });
+
+    let body = quote!(#magic #fields #pad);
+    match variant_opts.length_prefix_type() {
+        Some(prefix_type) => Ok(write_prefixed(prefix_type, &opts, body)),
+        None => Ok(body),
     }
 }
 
 
-fn write_for_type(ty: &Type, name: &TokenStream, approach: &TokenStream, prefix_length: Option<Type>) -> TokenStream {
+fn write_for_type(ty: &Type, name: &TokenStream, approach: &Approach, prefix_length: Option<Type>) -> TokenStream {
     if let Some(prefix) = prefix_length {
-        return quote_spanned!(ty.span() =>
-            ::io_self::derive_util::write_with_prefix::<#prefix, #ty, _, _, _, _>(
+        if util::is_string(ty) {
+            return write_string(ty, name, approach, &prefix);
+        }
+
+        return match approach.mode() {
+            Mode::Blocking => quote_spanned!(ty.span() =>
+                ::io_self::derive_util::write_with_prefix::<#prefix, #ty, _, _, _, _>(
+                    #name,
+                    __buffer,
+                    <_ as #approach>::write_to,
+                    <_ as #approach>::write_to)?;
+            ),
+            Mode::Async => {
+                let length_prefix = util::try_from(&prefix, &parse_quote!(usize), &quote!(count));
+                quote_spanned!(ty.span() => {
+                    let count = ::std::iter::IntoIterator::into_iter(#name).count();
+                    let length_prefix: #prefix = #length_prefix;
+                    <#prefix as #approach>::write_to_async(&length_prefix, __buffer).await?;
+
+                    for item in #name {
+                        <_ as #approach>::write_to_async(item, __buffer).await?;
+                    }
+                })
+            }
+        };
+    }
+
+    approach.write_call(ty, name)
+}
+
+fn write_string(ty: &Type, name: &TokenStream, approach: &Approach, prefix: &Type) -> TokenStream {
+    match approach.mode() {
+        Mode::Blocking => quote_spanned!(ty.span() =>
+            ::io_self::derive_util::write_str_with_prefix::<#prefix, _, _>(
                 #name,
-                buffer,
-                <_ as #approach>::write_to,
+                __buffer,
                 <_ as #approach>::write_to)?;
-        );
+        ),
+        Mode::Async => {
+            let length_prefix = util::try_from(prefix, &parse_quote!(usize), &quote!((#name).len()));
+            quote_spanned!(ty.span() => {
+                let length_prefix: #prefix = #length_prefix;
+                <#prefix as #approach>::write_to_async(&length_prefix, __buffer).await?;
+                ::io_self::derive_util::tokio::io::AsyncWriteExt::write_all(__buffer, (#name).as_bytes()).await?;
+            })
+        }
     }
+}
 
-    match ty {
-        Type::Array(arr) => {
-            let arr_type = &*arr.elem;
-            let item = quote!(item);
-            let write_element = write_for_type(arr_type, &item, approach, None);
+/// Writes an enum field without its tag, which is written by the field named in `tag_from`.
+fn write_external(ty: &Type, name: &TokenStream, field_opts: &FieldOpts) -> TokenStream {
+    if field_opts.mode() == Mode::Async {
+        return quote_spanned!(ty.span() => compile_error!("tag_from is not supported by AsyncWriteSelf"););
+    }
+
+    let endian = field_opts.trait_usage(false).endian_type();
+    quote_spanned!(ty.span() =>
+        <#ty as ::io_self::derive_util::WriteSelfTagged<#endian>>::write_variant(#name, __buffer)?;
+    )
+}
 
-            quote_spanned!( name.span() => for #item in #name { #write_element } )
+/// Writes a field to the next `bits` bits of the current bit group.
+fn write_bits(ty: &Type, name: &TokenStream, bits: u32, mode: Mode) -> TokenStream {
+    if let Some(error) = util::check_bit_width(ty, bits) {
+        return quote!(#error;);
+    }
+
+    match mode {
+        Mode::Blocking => quote_spanned!(ty.span() => __bits.write::<#ty, _>(__buffer, #name, #bits)?;),
+        Mode::Async => quote_spanned!(ty.span() => __bits.write_async::<#ty, _>(__buffer, #name, #bits).await?;),
+    }
+}
+
+/// Writes a field with the `write_to` function of a `with` codec, preceded by the codec's
+/// `encoded_len` when the field has a length prefix.
+fn write_codec(ty: &Type, name: &TokenStream, codec: &Path, field_opts: &FieldOpts) -> TokenStream {
+    if field_opts.mode() == Mode::Async {
+        return quote_spanned!(ty.span() => compile_error!("with is not supported by AsyncWriteSelf"););
+    }
+
+    let approach = field_opts.trait_usage(false);
+    let endian = approach.endian_type();
+    let args = field_opts.codec_args(false);
+    let write = quote_spanned!(codec.span() => #codec::write_to::<#endian, _>(#name, __buffer, #args)?;);
+
+    match field_opts.length_prefix_type() {
+        Some(prefix) => {
+            let length = util::try_from(
+                &prefix,
+                &parse_quote!(usize),
+                &quote_spanned!(codec.span() => #codec::encoded_len::<#endian>(#name, #args)),
+            );
+            let write_prefix = write_for_type(&prefix, &quote!(&length_prefix), &approach, None);
+            quote!({
+                let length_prefix: #prefix = #length;
+                #write_prefix
+                #write
+            })
         }
-        Type::Tuple(tuple) => {
-            let fields = tuple.elems.iter().enumerate().map(|(idx, f)| {
-                let index = Index::from(idx);
-                let item_name = quote!(#name.#index);
-                write_for_type(f, &item_name, approach, None)
-            });
-            quote_spanned!(name.span() => #(#fields)* )
+        None => write,
+    }
+}
+
+/// Writes a string field stored with an `encoding`.
+fn write_text(ty: &Type, name: &TokenStream, field_opts: &FieldOpts, format: &TokenStream) -> TokenStream {
+    if field_opts.mode() == Mode::Async {
+        return quote_spanned!(ty.span() => compile_error!("encoding is not supported by AsyncWriteSelf"););
+    }
+
+    match field_opts.text_layout() {
+        TextLayout::Prefixed(prefix) => {
+            let approach = field_opts.trait_usage(false);
+            let unit = field_opts.length_unit();
+            quote_spanned!(ty.span() =>
+                #format.write_with_prefix::<#prefix, _, _>(#name, __buffer, #unit, <_ as #approach>::write_to)?;
+            )
         }
-        x => quote_spanned! {x.span() => <#x as #approach>::write_to(#name, buffer)?; }
+        TextLayout::Terminated(_) => quote_spanned!(ty.span() => #format.write_terminated(#name, __buffer)?;),
+        TextLayout::Fixed(size) => quote_spanned!(ty.span() => #format.write_fixed(#name, __buffer, #size)?;),
     }
 }
 
@@ -114,58 +303,75 @@ fn derive_field_match(data_fields: &Fields) -> TokenStream {
                 .unnamed
                 .iter()
                 .enumerate()
-                .map(|(idx, _)| TokenStream::from_str(TUPLE_NAME_PLACEHOLDER[idx]).unwrap());
+                .map(|(idx, f)| util::field_local(f, idx));
             quote_spanned!(data_fields.span() => ( #(#assigned_fields),* ) )
         }
         Fields::Unit => quote_spanned!(data_fields.span() => ),
     }
 }
 
-fn derive_write_fields(
-    data_fields: &Fields,
-    name: &TokenStream,
-    opts: &Opts,
-    use_placeholders: bool,
-) -> TokenStream {
-    match data_fields {
-        Fields::Named(fields) => {
-            let assigned_fields = fields.named.iter().map(|f| {
-                let ident = &f.ident;
-                let ident = if use_placeholders {
-                    quote!(#ident)
-                } else {
-                    quote!(#name.#ident)
-                };
-
-                let mut field_opts = FieldOpts::from_field(f).expect("Unexpect attribute fields");
-                field_opts.with_endian(opts);
-
-                match field_opts.write_fn(&ident) {
-                    Some(v) => v,
-                    None => write_for_type(&f.ty, &ident, &opts.trait_usage(false), field_opts.length_prefix_type()),
-                }
-            });
+fn derive_write_fields(data_fields: &Fields, opts: &Opts) -> darling::Result<TokenStream> {
+    let all_opts = util::field_opts(data_fields, opts)?;
+    let bit_groups = util::bit_groups(&all_opts);
 
-            quote_spanned!(data_fields.span() => #(#assigned_fields)*)
+    let mut errors = darling::Error::accumulator();
+    for field_opts in &all_opts {
+        if let (Some(map), None) = (field_opts.map_expr(), field_opts.write_map(&quote!())) {
+            errors.push(darling::Error::custom("fields with map or try_map require write_map to be written").with_span(map));
         }
-        Fields::Unnamed(fields) => {
-            let assigned_fields = fields.unnamed.iter().enumerate().map(|(idx, f)| {
-                let path = if use_placeholders {
-                    TokenStream::from_str(TUPLE_NAME_PLACEHOLDER[idx]).unwrap()
-                } else {
-                    let index = Index::from(idx);
-                    quote!(#name.#index)
-                };
-                let mut field_opts = FieldOpts::from_field(f).expect("Unexpect attribute fields");
-                field_opts.with_endian(opts);
-
-                match field_opts.write_fn(&path) {
-                    Some(v) => v,
-                    None => write_for_type(&f.ty, &path, &opts.trait_usage(false), field_opts.length_prefix_type()),
-                }
-            });
-            quote_spanned!(data_fields.span() => #(#assigned_fields)* )
+    }
+    errors.finish()?;
+
+    // The fields holding the tags of later enum fields are written with the tag of the active variant
+    let fields = data_fields.iter().collect::<Vec<_>>();
+    let mut variant_tags = vec![None; fields.len()];
+    for (idx, source) in util::tag_sources(data_fields, &all_opts)?.into_iter().enumerate() {
+        if let Some(source) = source {
+            let ty = &fields[idx].ty;
+            let endian = all_opts[idx].trait_usage(false).endian_type();
+            let local = util::field_local(fields[idx], idx);
+            variant_tags[source] = Some(quote_spanned!(ty.span() =>
+                <#ty as ::io_self::derive_util::WriteSelfTagged<#endian>>::variant_tag(#local)
+            ));
         }
-        Fields::Unit => quote_spanned!(data_fields.span() => ),
     }
-}
\ No newline at end of file
+
+    let writes = data_fields.iter().zip(&all_opts).zip(bit_groups).enumerate().map(|(idx, ((f, field_opts), group))| {
+        let local = util::field_local(f, idx);
+        // A mapped field is written as the value returned by `write_map`
+        let (wire, path, ty) = match (&variant_tags[idx], field_opts.write_map(&local)) {
+            // The stored value is replaced, but still used to avoid an unused variable warning
+            (Some(tag), _) => (Some(quote!(let _ = #local; let __wire = #tag;)), quote!(&__wire), f.ty.clone()),
+            (None, Some(value)) => (Some(quote!(let __wire = #value;)), quote!(&__wire), parse_quote!(_)),
+            (None, None) => (None, local, f.ty.clone()),
+        };
+
+        let write = match (field_opts.tag_from(), field_opts.write_fn(&path), field_opts.codec(), field_opts.text_format(), field_opts.bits()) {
+            (Some(_), _, _, _, _) => write_external(&ty, &path, field_opts),
+            (None, Some(v), _, _, _) => v,
+            (None, None, Some(codec), _, _) => write_codec(&ty, &path, codec, field_opts),
+            (None, None, None, Some(format), _) => write_text(&ty, &path, field_opts, &format),
+            (None, None, None, None, Some(bits)) => write_bits(&ty, &path, bits, field_opts.mode()),
+            // Like reading, a string needs a layout so it can be read back
+            (None, None, None, None, None) if util::is_string(&ty) && field_opts.length_prefix_type().is_none() => {
+                quote_spanned!(ty.span() => compile_error!("String fields require one of length_prefix, null_terminated or fixed_size");)
+            }
+            (None, None, None, None, None) => write_for_type(&ty, &path, &field_opts.trait_usage(false), field_opts.length_prefix_type()),
+        };
+        let write = match wire {
+            Some(wire) => quote!({ #wire #write }),
+            None => write,
+        };
+        let start_group = group.start.then(|| {
+            let order = opts.bit_order();
+            quote!(let mut __bits = ::io_self::bits::BitWriter::new(#order);)
+        });
+        let end_group = group.end.map(|total| {
+            util::check_bit_group(total, f.span()).unwrap_or_else(|| quote!(__bits.finish()?;))
+        });
+
+        quote_spanned!(f.span() => #start_group #write #end_group)
+    });
+
+    Ok(quote_spanned!(data_fields.span() => #(#writes)*))
+}
@@ -1,4 +1,5 @@
 #![allow(dead_code)]
+use io_self::{ReadSelf, WriteSelf};
 use io_self_derive::{ReadSelf, WriteSelf};
 
 #[derive(ReadSelf, WriteSelf)]
@@ -20,4 +21,18 @@ pub struct Foo {
 #[io_self(endian = "big")]
 pub struct Bar(u8, u16, u32, u64, u128, i8, i16, i32, i64, i128);
 
-fn main() {}
+// A field's own endianness is used both when reading and writing
+#[derive(ReadSelf, WriteSelf, Debug, PartialEq)]
+#[io_self(endian = "big")]
+pub struct Mixed {
+    a: u16,
+    #[io_self(endian = "little")]
+    b: u16,
+}
+
+fn main() {
+    let mixed = Mixed { a: 0x0102, b: 0x0304 };
+    let bytes = mixed.to_bytes().unwrap();
+    assert_eq!(bytes, [1, 2, 4, 3]);
+    assert_eq!(Mixed::from_bytes(&bytes).unwrap(), mixed);
+}
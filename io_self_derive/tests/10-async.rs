@@ -0,0 +1,57 @@
+#![allow(dead_code)]
+use io_self::positional::{AsyncReadCounter, AsyncWriteCounter};
+use io_self::{AsyncReadSelf, AsyncWriteSelf, PositionAware, ReadSelf, WriteSelf};
+use io_self_derive::{AsyncReadSelf, AsyncWriteSelf, ReadSelf, WriteSelf};
+use std::io::Cursor;
+
+#[derive(ReadSelf, WriteSelf, AsyncReadSelf, AsyncWriteSelf, Debug, PartialEq)]
+#[io_self(endian = "big")]
+pub struct Foo {
+    a: u32,
+    #[io_self(length_prefix = "u16")]
+    b: Vec<u16>,
+    c: (i8, [u64; 2]),
+    #[io_self(length_prefix = "u8")]
+    d: String,
+}
+
+#[derive(ReadSelf, WriteSelf, AsyncReadSelf, AsyncWriteSelf, Debug, PartialEq)]
+#[io_self(endian = "little", tag = "u8", length_prefix = "u32")]
+pub enum Bar {
+    #[io_self(tag = "0x01")]
+    Fizz(u16, Box<Foo>),
+    #[io_self(tag = "0x02")]
+    Buzz { a: u8, b: i64 },
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() {
+    let values = vec![
+        Bar::Fizz(
+            0x1234,
+            Box::new(Foo {
+                a: 0xDEADBEEF,
+                b: vec![1, 2, 3],
+                c: (-1, [5, 6]),
+                d: "async".to_string(),
+            }),
+        ),
+        Bar::Buzz { a: 7, b: -8 },
+    ];
+
+    for value in values {
+        let mut blocking = Cursor::new(Vec::new());
+        value.write_to(&mut blocking).unwrap();
+
+        let mut writer = AsyncWriteCounter::new(Vec::new());
+        value.write_to_async(&mut writer).await.unwrap();
+        let bytes = blocking.into_inner();
+        assert_eq!(writer.position().unwrap(), bytes.len() as u64);
+
+        let mut reader = AsyncReadCounter::new(&bytes[..]);
+        let decoded = Bar::read_from_async(&mut reader).await.unwrap();
+        assert_eq!(reader.position().unwrap(), bytes.len() as u64);
+        assert_eq!(decoded, value);
+        assert_eq!(Bar::from_bytes(&bytes).unwrap(), value);
+    }
+}
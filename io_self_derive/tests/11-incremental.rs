@@ -0,0 +1,50 @@
+#![allow(dead_code)]
+use io_self::incremental::{Decoded, IncrementalDecoder};
+use io_self::WriteSelf;
+use io_self_derive::{ReadSelf, WriteSelf};
+use std::io::Cursor;
+
+#[derive(ReadSelf, WriteSelf, Debug, PartialEq)]
+#[io_self(endian = "little")]
+pub struct Foo {
+    a: u32,
+    #[io_self(length_prefix = "u8")]
+    b: Vec<u16>,
+}
+
+fn main() {
+    let values = [
+        Foo { a: 1, b: vec![2, 3] },
+        Foo { a: 4, b: vec![] },
+        Foo { a: 5, b: vec![6] },
+    ];
+
+    let mut bytes = Cursor::new(Vec::new());
+    for value in &values {
+        value.write_to(&mut bytes).unwrap();
+    }
+    let bytes = bytes.into_inner();
+
+    let mut decoder = IncrementalDecoder::<Foo>::new();
+    assert_eq!(decoder.decode().unwrap(), Decoded::NeedMore(4));
+
+    // Feed the data one byte at a time and check the values come out in order
+    let mut decoded = Vec::new();
+    for byte in &bytes {
+        decoder.feed(&[*byte]);
+        while let Decoded::Value(value) = decoder.decode().unwrap() {
+            decoded.push(value);
+        }
+    }
+
+    assert_eq!(&decoded[..], &values[..]);
+    assert!(decoder.buffered().is_empty());
+
+    // A partial value should not lose any of its bytes
+    decoder.feed(&bytes[..6]);
+    assert_eq!(decoder.decode().unwrap(), Decoded::NeedMore(1));
+    assert_eq!(decoder.buffered(), &bytes[..6]);
+    decoder.feed(&bytes[6..9]);
+    assert_eq!(decoder.decode().unwrap(), Decoded::Value(Foo { a: 1, b: vec![2, 3] }));
+    assert_eq!(decoder.into_inner(), Vec::<u8>::new());
+}
@@ -0,0 +1,48 @@
+#![allow(dead_code)]
+use bytes::BytesMut;
+use io_self::codec::{IoSelfCodec, LengthPrefix};
+use io_self_derive::{ReadSelf, WriteSelf};
+use std::io::ErrorKind;
+use tokio_util::codec::{Decoder, Encoder};
+
+#[derive(ReadSelf, WriteSelf, Debug, PartialEq)]
+#[io_self(endian = "big", tag = "u8")]
+pub enum Message {
+    #[io_self(tag = "0x01")]
+    Ping(u32),
+    #[io_self(tag = "0x02")]
+    Data(#[io_self(length_prefix = "u16")] Vec<u8>),
+}
+
+fn main() {
+    let mut codec = IoSelfCodec::<Message>::new()
+        .length_prefix(LengthPrefix::U16)
+        .little_endian()
+        .max_frame_length(16);
+
+    let mut buffer = BytesMut::new();
+    codec.encode(Message::Ping(7), &mut buffer).unwrap();
+    codec.encode(Message::Data(vec![1, 2, 3]), &mut buffer).unwrap();
+    assert_eq!(&buffer[..7], &[5, 0, 1, 0, 0, 0, 7]);
+
+    // Oversized frames are rejected without leaving partial data behind
+    let err = codec.encode(Message::Data(vec![0; 32]), &mut buffer).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+    assert_eq!(buffer.len(), 7 + 8);
+
+    // Frames are only decoded once they have been fully received
+    let mut partial = buffer.split_to(10);
+    assert_eq!(codec.decode(&mut partial).unwrap(), Some(Message::Ping(7)));
+    assert_eq!(codec.decode(&mut partial).unwrap(), None);
+    partial.unsplit(buffer);
+    assert_eq!(codec.decode(&mut partial).unwrap(), Some(Message::Data(vec![1, 2, 3])));
+    assert!(partial.is_empty());
+
+    let mut oversized = BytesMut::from(&[0xFF, 0x00][..]);
+    let err = codec.decode(&mut oversized).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+
+    let mut trailing = BytesMut::from(&[6, 0, 1, 0, 0, 0, 7, 0][..]);
+    let err = codec.decode(&mut trailing).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+}
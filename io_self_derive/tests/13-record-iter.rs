@@ -0,0 +1,34 @@
+#![allow(dead_code)]
+use io_self::positional::ReadCounter;
+use io_self::ReadSelf;
+use io_self_derive::ReadSelf;
+use std::io::{Cursor, ErrorKind};
+
+#[derive(ReadSelf, Debug, PartialEq)]
+#[io_self(endian = "big")]
+pub struct Record {
+    kind: u8,
+    value: u16,
+}
+
+fn main() {
+    let bytes = [1, 0, 2, 3, 0, 4];
+
+    let records = Record::iter_from(Cursor::new(&bytes[..]))
+        .collect::<std::io::Result<Vec<_>>>()
+        .unwrap();
+    assert_eq!(
+        records,
+        vec![Record { kind: 1, value: 2 }, Record { kind: 3, value: 4 }]
+    );
+
+    // Ending part way through a record is still an error
+    let mut iter = Record::iter_from(ReadCounter::new(&bytes[..5]));
+    assert_eq!(iter.next().unwrap().unwrap(), Record { kind: 1, value: 2 });
+    assert_eq!(iter.next().unwrap().unwrap_err().kind(), ErrorKind::UnexpectedEof);
+    assert!(iter.next().is_none());
+
+    let mut cursor = Cursor::new(&bytes[..3]);
+    assert!(Record::try_read_from(&mut cursor).unwrap().is_some());
+    assert!(Record::try_read_from(&mut cursor).unwrap().is_none());
+}
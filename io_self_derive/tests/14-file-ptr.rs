@@ -0,0 +1,80 @@
+#![allow(dead_code)]
+use io_self::pointer::FilePtr;
+use io_self::positional::ReadCounter;
+use io_self::{ReadSelf, WriteSelf};
+use io_self_derive::{ReadSelf, WriteSelf};
+use std::io::{Cursor, ErrorKind};
+
+#[derive(ReadSelf, WriteSelf, Debug, PartialEq)]
+#[io_self(endian = "big")]
+pub struct Entry {
+    kind: u16,
+    value: u16,
+}
+
+#[derive(ReadSelf, WriteSelf)]
+#[io_self(endian = "big")]
+pub struct Header {
+    magic: u8,
+    table_base: u32,
+    #[io_self(follow)]
+    absolute: FilePtr<u32, Entry>,
+    #[io_self(offset_base = "struct", follow)]
+    relative: FilePtr<u8, Entry>,
+    #[io_self(offset_base = "table_base")]
+    lazy: FilePtr<u16, Entry>,
+}
+
+/// Pointers can also be used without an endianness when the offset does not need one.
+#[derive(ReadSelf, WriteSelf)]
+pub struct Index {
+    #[io_self(follow)]
+    first: FilePtr<u8, u8>,
+    second: FilePtr<u8, u8>,
+}
+
+fn main() {
+    let mut bytes = vec![0xAA; 4];
+    bytes.extend_from_slice(&[0x7F, 0, 0, 0, 20, 0, 0, 0, 16, 16, 0, 4]);
+    bytes.extend_from_slice(&[0, 1, 0, 2, 0, 3, 0, 4, 0, 5, 0, 6]);
+
+    let mut cursor = Cursor::new(&bytes[..]);
+    cursor.set_position(4);
+    let mut header = Header::read_from(&mut cursor).unwrap();
+    assert_eq!(cursor.position(), 16);
+
+    assert_eq!(header.absolute.get(), Some(&Entry { kind: 1, value: 2 }));
+    assert_eq!(header.relative.get(), Some(&Entry { kind: 3, value: 4 }));
+    assert!(!header.lazy.is_resolved());
+    assert_eq!(header.lazy.target().unwrap(), 24);
+    assert_eq!(
+        header.lazy.resolve(&mut cursor).unwrap(),
+        &Entry { kind: 5, value: 6 }
+    );
+    assert_eq!(cursor.position(), 16);
+
+    // Resolved targets need a PointerWriter, so remove them to only write the offsets back out
+    let err = header.write_to(&mut Cursor::new(Vec::new())).err().unwrap();
+    assert_eq!(err.kind(), ErrorKind::InvalidInput);
+    header.absolute.take();
+    header.relative.take();
+    header.lazy.take();
+
+    let mut written = Cursor::new(Vec::new());
+    header.write_to(&mut written).unwrap();
+    assert_eq!(&written.into_inner()[..], &bytes[4..16]);
+
+    // Following a pointer requires a stream which can seek
+    let err = Header::read_from(&mut ReadCounter::new(&bytes[4..])).err().unwrap();
+    assert_eq!(err.kind(), ErrorKind::Unsupported);
+
+    let mut index = Index::read_from(&mut Cursor::new([3u8, 2, 7, 9])).unwrap();
+    assert_eq!(index.first.get(), Some(&9));
+    assert_eq!(index.second.resolve(&mut Cursor::new([3u8, 2, 7, 9])).unwrap(), &7);
+
+    index.first.take();
+    index.second.take();
+    let mut written = Cursor::new(Vec::new());
+    index.write_to(&mut written).unwrap();
+    assert_eq!(written.into_inner(), vec![3, 2]);
+}
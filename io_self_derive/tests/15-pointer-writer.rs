@@ -0,0 +1,88 @@
+#![allow(dead_code)]
+use io_self::pointer::{FilePtr, PointerWriter};
+use io_self::positional::WriteCounter;
+use io_self::{ReadSelf, WriteSelf};
+use io_self_derive::{ReadSelf, WriteSelf};
+use std::io::{Cursor, ErrorKind};
+
+#[derive(ReadSelf, WriteSelf, Debug, PartialEq)]
+#[io_self(endian = "little")]
+pub struct Entry {
+    kind: u8,
+    value: u32,
+}
+
+#[derive(ReadSelf, WriteSelf, Debug, PartialEq)]
+#[io_self(endian = "little")]
+pub struct Node {
+    id: u8,
+    #[io_self(follow)]
+    entry: FilePtr<u32, Entry>,
+}
+
+#[derive(ReadSelf, WriteSelf)]
+#[io_self(endian = "little")]
+pub struct Header {
+    magic: [u8; 3],
+    #[io_self(follow)]
+    first: FilePtr<u16, Entry>,
+    #[io_self(follow)]
+    second: FilePtr<u32, Node>,
+}
+
+#[derive(ReadSelf, WriteSelf)]
+#[io_self(endian = "little", tag = "u8")]
+pub enum Record {
+    // Targets within a length prefixed body are still laid out by the outer writer
+    #[io_self(tag = 1, length_prefix = "u8")]
+    Linked { id: u8, entry: FilePtr<u16, Entry> },
+}
+
+fn main() {
+    let header = Header {
+        magic: *b"PTR",
+        first: FilePtr::new(0).with_value(Entry { kind: 1, value: 2 }),
+        second: FilePtr::new(0).with_value(Node {
+            id: 3,
+            entry: FilePtr::new(0).with_value(Entry { kind: 4, value: 5 }),
+        }),
+    };
+
+    let mut writer = PointerWriter::new(Cursor::new(Vec::new())).with_alignment(4);
+    header.write_to(&mut writer).unwrap();
+    let bytes = writer.finish().unwrap().into_inner();
+
+    // Each target is aligned and laid out in the order its pointer was written
+    assert_eq!(&bytes[..9], &[b'P', b'T', b'R', 12, 0, 20, 0, 0, 0]);
+    assert_eq!(&bytes[12..17], &[1, 2, 0, 0, 0]);
+    assert_eq!(&bytes[20..25], &[3, 28, 0, 0, 0]);
+    assert_eq!(&bytes[28..], &[4, 5, 0, 0, 0]);
+
+    // Counters which continue from a pointer writer forward its layout
+    let mut counter = WriteCounter::continuing(PointerWriter::new(Cursor::new(Vec::new())).with_alignment(4)).unwrap();
+    header.write_to(&mut counter).unwrap();
+    assert_eq!(counter.into_inner().finish().unwrap().into_inner(), bytes);
+
+    let decoded = Header::read_from(&mut Cursor::new(&bytes)).unwrap();
+    assert_eq!(decoded.first.get(), header.first.get());
+    assert_eq!(
+        decoded.second.get().unwrap().entry.get(),
+        Some(&Entry { kind: 4, value: 5 })
+    );
+
+    let record = Record::Linked {
+        id: 7,
+        entry: FilePtr::new(0).with_value(Entry { kind: 8, value: 9 }),
+    };
+
+    let mut writer = PointerWriter::new(Cursor::new(Vec::new()));
+    record.write_to(&mut writer).unwrap();
+    let bytes = writer.finish().unwrap().into_inner();
+    assert_eq!(&bytes[..], &[1, 3, 7, 5, 0, 8, 9, 0, 0, 0]);
+
+    // A resolved target can not be written to a stream which can not lay it out
+    let err = record.write_to(&mut Cursor::new(Vec::new())).err().unwrap();
+    assert_eq!(err.kind(), ErrorKind::InvalidInput);
+    let err = header.write_to(&mut Cursor::new(Vec::new())).err().unwrap();
+    assert_eq!(err.kind(), ErrorKind::InvalidInput);
+}
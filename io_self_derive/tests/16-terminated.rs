@@ -0,0 +1,65 @@
+#![allow(dead_code)]
+use io_self::grammar::{read_cstring_buffered, ByteTerminatedVec, NullString, TerminatedString, CSTRING_MAX_LEN};
+use io_self::positional::ReadCounter;
+use io_self::{ReadSelf, WriteSelf};
+use io_self_derive::{ReadSelf, WriteSelf};
+use std::ffi::CString;
+use std::io::{BufReader, Cursor, ErrorKind};
+
+#[derive(ReadSelf, WriteSelf, Debug, PartialEq)]
+pub struct Record {
+    name: NullString<16>,
+    raw: CString,
+    line: ByteTerminatedVec<u8, b'\n'>,
+    path: TerminatedString<b'/'>,
+}
+
+fn main() {
+    let record = Record {
+        name: "hello".into(),
+        raw: CString::new(vec![0xFF, 0xFE]).unwrap(),
+        line: vec![1, 2, 3].into(),
+        path: "usr".into(),
+    };
+
+    let mut bytes = Cursor::new(Vec::new());
+    record.write_to(&mut bytes).unwrap();
+    let bytes = bytes.into_inner();
+    assert_eq!(&bytes[..], b"hello\0\xFF\xFE\0\x01\x02\x03\nusr/");
+    assert_eq!(Record::from_bytes(&bytes).unwrap(), record);
+
+    // The buffered fast path reads the same value without consuming past the terminator
+    let mut reader = ReadCounter::new(BufReader::with_capacity(4, &bytes[..]));
+    let name = NullString::<16>::read_buffered(&mut reader).unwrap();
+    assert_eq!(name.as_str(), "hello");
+    let raw = read_cstring_buffered(&mut reader).unwrap();
+    assert_eq!(raw.as_bytes(), &[0xFF, 0xFE]);
+    assert_eq!(io_self::PositionAware::position(&mut reader).unwrap(), 9);
+
+    let err = NullString::<16>::from_bytes(b"ab\xFFcd\0").unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+    assert_eq!(err.to_string(), "invalid UTF-8 at offset 2");
+
+    // Unterminated input is rejected once it exceeds the maximum length
+    let err = NullString::<4>::from_bytes(b"abcdefgh").unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+    let err = NullString::<4>::read_buffered(&mut Cursor::new(b"abcdefgh")).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+    let err = CString::from_bytes(b"abc").unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+    let err = CString::from_bytes(&vec![b'a'; CSTRING_MAX_LEN + 1]).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+    let err = <NullString>::from_bytes(&vec![b'a'; CSTRING_MAX_LEN + 1]).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+    let err = ByteTerminatedVec::<u8>::from_bytes(&vec![1; CSTRING_MAX_LEN + 1]).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+
+    // Values which do not read any bytes can not fill a terminated list
+    let err = ByteTerminatedVec::<()>::from_bytes(b"ab\0").unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+
+    let err = NullString::<16>::from("a\0b")
+        .write_to(&mut Cursor::new(Vec::new()))
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidInput);
+}
@@ -0,0 +1,50 @@
+#![allow(dead_code)]
+use io_self::grammar::{FixedStr, NullTerminated, SpacePadded, SpaceTrimmed};
+use io_self::{ReadSelf, WriteSelf};
+use io_self_derive::{ReadSelf, WriteSelf};
+use std::io::{Cursor, ErrorKind};
+
+#[derive(ReadSelf, WriteSelf, Debug, PartialEq)]
+#[io_self(endian = "big")]
+pub struct Header {
+    #[io_self(length_prefix = "u16")]
+    name: String,
+    magic: FixedStr<4>,
+    fat_name: FixedStr<8, SpacePadded>,
+    tar_name: FixedStr<6, NullTerminated>,
+    dicom: FixedStr<6, SpaceTrimmed>,
+}
+
+fn main() {
+    let header = Header {
+        name: "héllo".to_string(),
+        magic: "AB".into(),
+        fat_name: "README".into(),
+        tar_name: "a.txt".into(),
+        dicom: "CT".into(),
+    };
+
+    let mut bytes = Cursor::new(Vec::new());
+    header.write_to(&mut bytes).unwrap();
+    let bytes = bytes.into_inner();
+    assert_eq!(&bytes[..8], b"\x00\x06h\xC3\xA9llo");
+    assert_eq!(&bytes[8..], b"AB\0\0README  a.txt\0CT    ");
+    assert_eq!(Header::from_bytes(&bytes).unwrap(), header);
+
+    // Trimming rules only remove the padding they are configured for
+    assert_eq!(FixedStr::<6, NullTerminated>::from_bytes(b"ab\0cd\0").unwrap().as_str(), "ab");
+    assert_eq!(FixedStr::<6>::from_bytes(b"ab\0cd\0").unwrap().as_str(), "ab\0cd");
+    assert_eq!(FixedStr::<6, SpaceTrimmed>::from_bytes(b" ab c\0").unwrap().as_str(), "ab c");
+
+    // UTF-8 errors include the position in the stream
+    let err = Header::from_bytes(b"\x00\x03ab\xFF").unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+    assert_eq!(err.to_string(), "invalid UTF-8 at offset 4");
+    let err = FixedStr::<4, SpaceTrimmed>::from_bytes(b"  \xFFa").unwrap_err();
+    assert_eq!(err.to_string(), "invalid UTF-8 at offset 2");
+
+    let err = FixedStr::<2, SpacePadded>::from("abc")
+        .write_to(&mut Cursor::new(Vec::new()))
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidInput);
+}
@@ -0,0 +1,76 @@
+#![allow(dead_code)]
+use io_self::grammar::{Leb128, Sleb128, VarI64, VarU32, VarU64, Vlq, ZigZag};
+use io_self::{ReadSelf, WriteSelf};
+use io_self_derive::{ReadSelf, WriteSelf};
+use std::io::ErrorKind;
+
+#[derive(ReadSelf, WriteSelf, Debug, PartialEq)]
+pub struct Message {
+    #[io_self(length_prefix = "VarU32")]
+    items: Vec<VarI64<ZigZag>>,
+    #[io_self(length_prefix = "VarU64<Vlq>")]
+    name: String,
+}
+
+#[derive(ReadSelf, WriteSelf, Debug, PartialEq)]
+#[io_self(tag = "VarU32", length_prefix = "VarU32")]
+pub enum Field {
+    #[io_self(tag = "1")]
+    Small(u8),
+    #[io_self(tag = "300")]
+    Large(VarU64),
+}
+
+fn check<T: ReadSelf + WriteSelf + Copy + PartialEq + std::fmt::Debug>(value: T, bytes: &[u8]) {
+    assert_eq!(value.to_bytes().unwrap(), bytes);
+    assert_eq!(T::from_bytes(&bytes).unwrap(), value);
+}
+
+fn expect_err<T: ReadSelf + std::fmt::Debug>(bytes: &[u8], message: &str) {
+    let err = T::from_bytes(&bytes).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+    assert_eq!(err.to_string(), message);
+}
+
+fn main() {
+    check(VarU32::<Leb128>::new(0), &[0x00]);
+    check(VarU32::<Leb128>::new(300), &[0xAC, 0x02]);
+    check(VarU32::<Leb128>::new(u32::MAX), &[0xFF, 0xFF, 0xFF, 0xFF, 0x0F]);
+    check(VarU64::<Vlq>::new(0), &[0x00]);
+    check(VarU64::<Vlq>::new(0x80), &[0x81, 0x00]);
+    check(VarU32::<Vlq>::new(0x0FFF_FFFF), &[0xFF, 0xFF, 0xFF, 0x7F]);
+    check(VarI64::<Sleb128>::new(-1), &[0x7F]);
+    check(VarI64::<Sleb128>::new(64), &[0xC0, 0x00]);
+    check(VarI64::<Sleb128>::new(-123456), &[0xC0, 0xBB, 0x78]);
+    check(VarI64::<Sleb128>::new(i64::MIN), &[0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x7F]);
+    check(VarI64::<Sleb128>::new(i64::MAX), &[0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x00]);
+    check(VarI64::<ZigZag>::new(-1), &[0x01]);
+    check(VarI64::<ZigZag>::new(-64), &[0x7F]);
+    check(VarI64::<ZigZag>::new(i64::MIN), &[0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x01]);
+
+    // Overflows and overlong encodings are rejected
+    expect_err::<VarU32>(&[0xFF, 0xFF, 0xFF, 0xFF, 0x10], "varint does not fit in 32 bits");
+    expect_err::<VarU32>(&[0x80, 0x80, 0x80, 0x80, 0x80, 0x00], "varint does not fit in 32 bits");
+    expect_err::<VarU32>(&[0x80, 0x00], "overlong varint encoding");
+    expect_err::<VarU32<Vlq>>(&[0x80, 0x01], "overlong varint encoding");
+    expect_err::<VarU32<Vlq>>(&[0x90, 0x80, 0x80, 0x80, 0x00], "varint does not fit in 32 bits");
+    expect_err::<VarI64>(&[0xFF, 0x7F], "overlong varint encoding");
+    expect_err::<VarI64>(&[0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x01], "varint does not fit in 64 bits");
+
+    let message = Message {
+        items: vec![1.into(), (-2).into()],
+        name: "vlq".to_string(),
+    };
+    let bytes = message.to_bytes().unwrap();
+    assert_eq!(bytes, b"\x02\x02\x03\x03vlq");
+    assert_eq!(Message::from_bytes(&bytes).unwrap(), message);
+
+    for (field, expected) in [
+        (Field::Small(7), &b"\x02\x01\x07"[..]),
+        (Field::Large(VarU64::new(1)), &b"\x03\xAC\x02\x01"[..]),
+    ] {
+        let bytes = field.to_bytes().unwrap();
+        assert_eq!(bytes, expected);
+        assert_eq!(Field::from_bytes(&bytes).unwrap(), field);
+    }
+}
@@ -0,0 +1,70 @@
+#![allow(dead_code)]
+use io_self::bits::{BitOrder, BitReader};
+use io_self::positional::{AsyncReadCounter, AsyncWriteCounter};
+use io_self::{AsyncReadSelf, AsyncWriteSelf, ReadSelf, WriteSelf};
+use io_self_derive::{AsyncReadSelf, AsyncWriteSelf, ReadSelf, WriteSelf};
+use std::io::ErrorKind;
+
+#[derive(ReadSelf, WriteSelf, AsyncReadSelf, AsyncWriteSelf, Debug, PartialEq)]
+#[io_self(endian = "big")]
+pub struct Ipv4Prefix {
+    #[io_self(bits = 4)]
+    version: u8,
+    #[io_self(bits = 4)]
+    ihl: u8,
+    tos: u8,
+    total_length: u16,
+    identification: u16,
+    #[io_self(bits = 3)]
+    flags: u8,
+    #[io_self(bits = 13)]
+    fragment_offset: u16,
+}
+
+#[derive(ReadSelf, WriteSelf, Debug, PartialEq)]
+#[io_self(bit_order = "lsb")]
+pub struct Packed(
+    #[io_self(bits = 1)] bool,
+    #[io_self(bits = 3)] u8,
+    #[io_self(bits = 4)] i8,
+);
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() {
+    let header = Ipv4Prefix {
+        version: 4,
+        ihl: 5,
+        tos: 0,
+        total_length: 0x54,
+        identification: 0x1234,
+        flags: 0b010,
+        fragment_offset: 0x0123,
+    };
+
+    let bytes = header.to_bytes().unwrap();
+    assert_eq!(bytes, [0x45, 0x00, 0x00, 0x54, 0x12, 0x34, 0x41, 0x23]);
+    assert_eq!(Ipv4Prefix::from_bytes(&bytes).unwrap(), header);
+
+    let mut writer = AsyncWriteCounter::new(Vec::new());
+    header.write_to_async(&mut writer).await.unwrap();
+    let mut reader = AsyncReadCounter::new(&bytes[..]);
+    assert_eq!(Ipv4Prefix::read_from_async(&mut reader).await.unwrap(), header);
+
+    // Least significant bits first, with the last field sign extended
+    let packed = Packed(true, 5, -2);
+    let bytes = packed.to_bytes().unwrap();
+    assert_eq!(bytes, [0xEB]);
+    assert_eq!(Packed::from_bytes(&bytes).unwrap(), packed);
+
+    // Values which do not fit in their field are rejected
+    let err = Packed(false, 8, 0).to_bytes().unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidInput);
+    assert_eq!(err.to_string(), "value does not fit in 3 bits");
+    assert!(Packed(false, 0, -9).to_bytes().is_err());
+
+    // Groups read by hand must still end on a byte boundary
+    let mut bits = BitReader::new(BitOrder::MsbFirst);
+    let nibble: u8 = bits.read(&mut &[0xAB][..], 4).unwrap();
+    assert_eq!(nibble, 0xA);
+    assert_eq!(bits.finish().unwrap_err().to_string(), "bit group ended with 4 unread bits");
+}
@@ -0,0 +1,58 @@
+#![allow(dead_code)]
+use io_self::grammar::{I24, U24, U40, U48, U56};
+use io_self::{ReadSelf, WriteSelf};
+use io_self_derive::{ReadSelf, WriteSelf};
+use std::io::ErrorKind;
+
+#[derive(ReadSelf, WriteSelf, Debug, PartialEq)]
+#[io_self(endian = "little")]
+pub struct Samples {
+    #[io_self(length_prefix = "U24")]
+    samples: Vec<I24>,
+    counter: U48,
+    wide: (U40, U56),
+}
+
+#[derive(ReadSelf, WriteSelf, Debug, PartialEq)]
+#[io_self(endian = "big", tag = "U24", length_prefix = "U24")]
+pub enum Handshake {
+    #[io_self(tag = "0x010203")]
+    Hello(u8),
+}
+
+fn main() {
+    let samples = Samples {
+        samples: vec![I24(-1), I24(I24::MAX), I24(I24::MIN)],
+        counter: U48(0x0102_0304_0506),
+        wide: (U40(U40::MAX), U56(1)),
+    };
+
+    let bytes = samples.to_bytes().unwrap();
+    let mut expected = vec![3, 0, 0, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x7F, 0x00, 0x00, 0x80];
+    expected.extend_from_slice(&[6, 5, 4, 3, 2, 1]);
+    expected.extend_from_slice(&[0xFF; 5]);
+    expected.extend_from_slice(&[1, 0, 0, 0, 0, 0, 0]);
+    assert_eq!(bytes, expected);
+    assert_eq!(Samples::from_bytes(&bytes).unwrap(), samples);
+
+    let bytes = Handshake::Hello(9).to_bytes().unwrap();
+    assert_eq!(bytes, [0, 0, 4, 1, 2, 3, 9]);
+    assert_eq!(Handshake::from_bytes(&bytes).unwrap(), Handshake::Hello(9));
+
+    // Values outside of the range of the type are rejected on write
+    let mut out_of_range = Samples {
+        samples: vec![I24(1 << 23)],
+        counter: U48(0),
+        wide: (U40(0), U56(0)),
+    };
+    let err = out_of_range.to_bytes().unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidInput);
+    assert_eq!(err.to_string(), "8388608 does not fit in 24 bits");
+
+    out_of_range.samples.clear();
+    out_of_range.counter = U48(1 << 48);
+    assert!(out_of_range.to_bytes().is_err());
+
+    assert!(U24::try_from(0x0100_0000u32).is_err());
+    assert_eq!(u32::from(U24::try_from(0xFF_FFFFu32).unwrap()), 0xFF_FFFF);
+}
@@ -0,0 +1,51 @@
+#![allow(dead_code)]
+use io_self::grammar::U24;
+use io_self::{Be, Le, ReadSelf, WriteSelf};
+use io_self_derive::{ReadSelf, WriteSelf};
+use std::io::Cursor;
+
+#[derive(ReadSelf, WriteSelf, Debug, PartialEq)]
+pub struct Mixed {
+    magic: Be<u32>,
+    #[io_self(length_prefix = "u8")]
+    values: Vec<Le<u16>>,
+    length: Be<U24>,
+}
+
+#[derive(ReadSelf, WriteSelf, Debug, PartialEq)]
+#[io_self(tag = "Be<u16>")]
+pub enum Record {
+    #[io_self(tag = "0x0102")]
+    Point(Le<i32>, Le<i32>),
+}
+
+fn main() {
+    let pair = <(Be<u32>, Le<u16>)>::from_bytes(&[0, 0, 1, 0, 2, 1]).unwrap();
+    assert_eq!(pair, (Be(0x100), Le(0x102)));
+    assert_eq!(*pair.0 + 1, 0x101);
+    assert_eq!(pair.to_bytes().unwrap(), [0, 0, 1, 0, 2, 1]);
+
+    let array = <[Be<i16>; 2]>::from_bytes(&[0xFF, 0xFE, 0x00, 0x01]).unwrap();
+    assert_eq!(array, [Be(-2), Be(1)]);
+
+    let words = u32::from_be_bytes(*b"abcd");
+    let records = Be::<u32>::iter_from(Cursor::new(b"abcdabcd"))
+        .map(|x| x.map(Be::into_inner))
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(records, [words, words]);
+
+    let mixed = Mixed {
+        magic: Be(0xCAFEBABE),
+        values: vec![1.into(), 2.into()],
+        length: Be(U24(0x010203)),
+    };
+    let bytes = mixed.to_bytes().unwrap();
+    assert_eq!(bytes, [0xCA, 0xFE, 0xBA, 0xBE, 2, 1, 0, 2, 0, 1, 2, 3]);
+    assert_eq!(Mixed::from_bytes(&bytes).unwrap(), mixed);
+
+    let record = Record::Point(Le(-1), Le(2));
+    let bytes = record.to_bytes().unwrap();
+    assert_eq!(bytes, [1, 2, 0xFF, 0xFF, 0xFF, 0xFF, 2, 0, 0, 0]);
+    assert_eq!(Record::from_bytes(&bytes).unwrap(), record);
+}
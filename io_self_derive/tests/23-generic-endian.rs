@@ -0,0 +1,75 @@
+#![allow(dead_code)]
+use io_self::endian::{BigEndian, LittleEndian, ReadSelfEndian, WriteSelfEndian};
+use io_self::positional::AsyncReadCounter;
+use io_self::{AsyncReadSelf, Be, Le, ReadSelf, WriteSelf};
+use io_self_derive::{AsyncReadSelf, AsyncWriteSelf, ReadSelf, WriteSelf};
+use std::io::Cursor;
+
+#[derive(ReadSelf, WriteSelf, AsyncReadSelf, AsyncWriteSelf, Debug, PartialEq, Clone)]
+#[io_self(endian = "generic")]
+pub struct Header {
+    magic: u16,
+    #[io_self(length_prefix = "u16")]
+    items: Vec<u32>,
+    #[io_self(endian = "little")]
+    fixed: u16,
+    pair: (i16, [u16; 2]),
+}
+
+#[derive(ReadSelf, WriteSelf, Debug, PartialEq)]
+#[io_self(endian = "generic")]
+pub struct Wrapper<T> {
+    count: u32,
+    inner: T,
+}
+
+#[derive(ReadSelf, WriteSelf, Debug, PartialEq)]
+#[io_self(endian = "big")]
+pub struct BigFile {
+    header: Header,
+}
+
+#[derive(ReadSelf, WriteSelf, Debug, PartialEq)]
+#[io_self(endian = "little")]
+pub struct LittleFile {
+    header: Header,
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() {
+    let header = Header {
+        magic: 0x0102,
+        items: vec![3],
+        fixed: 0x0405,
+        pair: (-2, [6, 7]),
+    };
+
+    let big = [1, 2, 0, 1, 0, 0, 0, 3, 5, 4, 0xFF, 0xFE, 0, 6, 0, 7];
+    let little = [2, 1, 1, 0, 3, 0, 0, 0, 5, 4, 0xFE, 0xFF, 6, 0, 7, 0];
+
+    let file = BigFile { header: header.clone() };
+    assert_eq!(file.to_bytes().unwrap(), big);
+    assert_eq!(BigFile::from_bytes(&big).unwrap(), file);
+
+    let file = LittleFile { header: header.clone() };
+    assert_eq!(file.to_bytes().unwrap(), little);
+    assert_eq!(LittleFile::from_bytes(&little).unwrap(), file);
+
+    // The parent can also be a wrapper or a call to the endian traits directly
+    assert_eq!(Be::<Header>::from_bytes(&big).unwrap().0, header);
+    let mut cursor = Cursor::new(&little[..]);
+    assert_eq!(<Header as ReadSelfEndian<LittleEndian>>::read_from(&mut cursor).unwrap(), header);
+
+    let wrapper = Le(Wrapper { count: 1, inner: header.clone() });
+    let bytes = wrapper.to_bytes().unwrap();
+    assert_eq!(&bytes[..4], [1, 0, 0, 0]);
+    assert_eq!(&bytes[4..], little);
+    assert_eq!(Le::<Wrapper<Header>>::from_bytes(&bytes).unwrap(), wrapper);
+
+    let mut cursor = Cursor::new(Vec::new());
+    <Header as WriteSelfEndian<BigEndian>>::write_to(&header, &mut cursor).unwrap();
+    assert_eq!(cursor.into_inner(), big);
+
+    let mut reader = AsyncReadCounter::new(&big[..]);
+    assert_eq!(Be::<Header>::read_from_async(&mut reader).await.unwrap().0, header);
+}
@@ -0,0 +1,78 @@
+#![allow(dead_code)]
+use io_self::positional::AsyncReadCounter;
+use io_self::{AsyncReadSelf, ReadSelf, WriteSelf};
+use io_self_derive::{AsyncReadSelf, AsyncWriteSelf, ReadSelf, WriteSelf};
+use std::rc::Rc;
+use std::sync::Arc;
+
+#[derive(ReadSelf, WriteSelf, AsyncReadSelf, AsyncWriteSelf, Debug, PartialEq, Clone, Copy)]
+pub struct Flag {
+    value: u8,
+}
+
+#[derive(ReadSelf, WriteSelf, Debug, PartialEq)]
+#[io_self(endian = "big")]
+pub struct Nested {
+    boxed: Box<[u16; 2]>,
+    shared: Rc<(u16, [i16; 1])>,
+    sync: Arc<u32>,
+    pairs: [(u16, Flag); 2],
+    #[io_self(until_eof)]
+    tail: Vec<u16>,
+}
+
+#[derive(ReadSelf, WriteSelf, Debug, PartialEq)]
+#[io_self(endian = "little")]
+pub struct Record {
+    id: u16,
+    flags: Box<[Flag; 2]>,
+    #[io_self(until_eof)]
+    checksum: Option<u32>,
+}
+
+#[derive(AsyncReadSelf, AsyncWriteSelf, Debug, PartialEq)]
+#[io_self(endian = "little")]
+pub struct AsyncRecord {
+    id: u16,
+    flags: Box<[Flag; 2]>,
+    pair: Arc<(u16, i16)>,
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() {
+    let nested = Nested {
+        boxed: Box::new([1, 2]),
+        shared: Rc::new((3, [-1])),
+        sync: Arc::new(4),
+        pairs: [(5, Flag { value: 6 }), (7, Flag { value: 8 })],
+        tail: vec![9, 10],
+    };
+    let bytes = [0, 1, 0, 2, 0, 3, 0xFF, 0xFF, 0, 0, 0, 4, 0, 5, 6, 0, 7, 8, 0, 9, 0, 10];
+    assert_eq!(nested.to_bytes().unwrap(), bytes);
+    assert_eq!(Nested::from_bytes(&bytes).unwrap(), nested);
+
+    // A vec marked until_eof reads every remaining value, and must not split one
+    assert!(Nested::from_bytes(&&bytes[..bytes.len() - 1]).is_err());
+
+    // An option is only absent when the stream ends before it
+    let record = Record {
+        id: 0x0102,
+        flags: Box::new([Flag { value: 3 }, Flag { value: 4 }]),
+        checksum: Some(0x05060708),
+    };
+    let bytes = [2, 1, 3, 4, 8, 7, 6, 5];
+    assert_eq!(record.to_bytes().unwrap(), bytes);
+    assert_eq!(Record::from_bytes(&bytes).unwrap(), record);
+
+    let record = Record { checksum: None, ..record };
+    assert_eq!(record.to_bytes().unwrap(), bytes[..4]);
+    assert_eq!(Record::from_bytes(&&bytes[..4]).unwrap(), record);
+    assert!(Record::from_bytes(&&bytes[..6]).is_err());
+
+    let bytes = [2, 1, 3, 4, 6, 5, 0xFE, 0xFF];
+    let mut reader = AsyncReadCounter::new(&bytes[..]);
+    let record = AsyncRecord::read_from_async(&mut reader).await.unwrap();
+    assert_eq!(record.id, 0x0102);
+    assert_eq!(*record.flags, [Flag { value: 3 }, Flag { value: 4 }]);
+    assert_eq!(*record.pair, (0x0506, -2));
+}
@@ -0,0 +1,59 @@
+#![allow(dead_code)]
+use io_self::{ReadSelf, WriteSelf};
+use io_self_derive::{ReadSelf, WriteSelf};
+use std::io::Cursor;
+
+mod codec {
+    use std::io::{self, Read, Write};
+
+    pub fn read_flag<B: Read>(buffer: &mut B) -> io::Result<bool> {
+        let mut byte = [0u8];
+        buffer.read_exact(&mut byte)?;
+        Ok(byte[0] == b'Y')
+    }
+
+    pub fn write_flag<B: Write>(value: &bool, buffer: &mut B) -> io::Result<()> {
+        buffer.write_all(if *value { b"Y" } else { b"N" })
+    }
+}
+
+const LONG_TAG: u8 = 0x3F;
+
+#[derive(ReadSelf, WriteSelf, Debug, PartialEq)]
+#[io_self(endian = big, tag = u8)]
+pub enum Message {
+    #[io_self(tag = 0x01)]
+    Short(u16),
+    #[io_self(tag = LONG_TAG)]
+    Long {
+        #[io_self(length_prefix = u16)]
+        items: Vec<u16>,
+        #[io_self(read_with = codec::read_flag, write_with = codec::write_flag)]
+        flag: bool,
+        #[io_self(encoding = latin1, length_prefix = u8, length_unit = bytes)]
+        name: String,
+    },
+    // The quoted forms are still accepted
+    #[io_self(tag = "0x02")]
+    Quoted(#[io_self(length_prefix = "u8", endian = "little")] Vec<u16>),
+}
+
+fn round_trip(message: Message, bytes: &[u8]) {
+    let mut cursor = Cursor::new(Vec::new());
+    message.write_to(&mut cursor).unwrap();
+    assert_eq!(cursor.into_inner(), bytes);
+    assert_eq!(Message::from_bytes(&bytes).unwrap(), message);
+}
+
+fn main() {
+    round_trip(Message::Short(0x0102), &[1, 1, 2]);
+    round_trip(
+        Message::Long {
+            items: vec![3, 4],
+            flag: true,
+            name: "é".to_string(),
+        },
+        &[0x3F, 0, 2, 0, 3, 0, 4, b'Y', 1, 0xE9],
+    );
+    round_trip(Message::Quoted(vec![5]), &[2, 1, 5, 0]);
+}
@@ -0,0 +1,119 @@
+#![allow(dead_code)]
+use io_self::endian::LittleEndian;
+use io_self::{Le, ReadSelf, WriteSelf};
+use io_self_derive::{ReadSelf, WriteSelf};
+use std::io::Cursor;
+use std::net::Ipv4Addr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Stores a duration as a number of ticks, where the tick length is given by an earlier field.
+mod ticks {
+    use io_self::endian::{ByteOrder, ReadSelfEndian, WriteSelfEndian};
+    use io_self::PositionAware;
+    use std::io::{self, Read, Write};
+    use std::time::Duration;
+
+    pub fn read_from<O: ByteOrder, B: Read + PositionAware>(buffer: &mut B, tick_ms: &u16) -> io::Result<Duration> {
+        let ticks = <u32 as ReadSelfEndian<O>>::read_from(buffer)?;
+        Ok(Duration::from_millis(ticks as u64 * *tick_ms as u64))
+    }
+
+    pub fn write_to<O: ByteOrder, B: Write + PositionAware>(
+        value: &Duration,
+        buffer: &mut B,
+        tick_ms: &u16,
+    ) -> io::Result<()> {
+        let ticks = (value.as_millis() / *tick_ms as u128) as u32;
+        <u32 as WriteSelfEndian<O>>::write_to(&ticks, buffer)
+    }
+
+    // The byte order is part of the codec signature even when the length does not depend on it
+    #[allow(clippy::extra_unused_type_parameters)]
+    pub fn encoded_len<O>(_: &Duration, _: &u16) -> usize {
+        4
+    }
+}
+
+/// XORs a byte with a key given by an earlier field, which is not `Copy`.
+// The args are a reference to the field itself, and the codec ignores the byte order
+#[allow(clippy::ptr_arg, clippy::extra_unused_type_parameters)]
+mod masked {
+    use io_self::PositionAware;
+    use std::io::{self, Read, Write};
+
+    pub fn read_from<O, B: Read + PositionAware>(buffer: &mut B, key: &Vec<u8>) -> io::Result<u8> {
+        let mut byte = [0];
+        buffer.read_exact(&mut byte)?;
+        Ok(key.iter().fold(byte[0], |byte, k| byte ^ k))
+    }
+
+    pub fn write_to<O, B: Write + PositionAware>(value: &u8, buffer: &mut B, key: &Vec<u8>) -> io::Result<()> {
+        buffer.write_all(&[key.iter().fold(*value, |byte, k| byte ^ k)])
+    }
+
+    pub fn encoded_len<O>(_: &u8, _: &Vec<u8>) -> usize {
+        1
+    }
+}
+
+#[derive(ReadSelf, WriteSelf, Debug, PartialEq)]
+pub struct Masked {
+    #[io_self(length_prefix = u8)]
+    key: Vec<u8>,
+    #[io_self(with = masked, args = key)]
+    value: u8,
+}
+
+#[derive(ReadSelf, WriteSelf, Debug, PartialEq)]
+#[io_self(endian = "big")]
+pub struct Lease {
+    #[io_self(with = io_self::with::ipv4)]
+    address: Ipv4Addr,
+    #[io_self(with = io_self::with::unix_timestamp)]
+    expires: SystemTime,
+    tick_ms: u16,
+    #[io_self(with = ticks, args = tick_ms)]
+    renew_after: Duration,
+    #[io_self(with = ticks, args = tick_ms, length_prefix = u8, endian = "little")]
+    rebind_after: Duration,
+}
+
+#[derive(ReadSelf, WriteSelf, Debug, PartialEq)]
+#[io_self(endian = "generic")]
+pub struct Route(#[io_self(with = io_self::with::ipv4)] Ipv4Addr, u8);
+
+fn main() {
+    let lease = Lease {
+        address: Ipv4Addr::new(192, 168, 0, 1),
+        expires: UNIX_EPOCH + Duration::from_secs(0x01020304),
+        tick_ms: 10,
+        renew_after: Duration::from_millis(500),
+        rebind_after: Duration::from_secs(1),
+    };
+    let bytes = [192, 168, 0, 1, 1, 2, 3, 4, 0, 10, 0, 0, 0, 50, 4, 100, 0, 0, 0];
+    assert_eq!(lease.to_bytes().unwrap(), bytes);
+    assert_eq!(Lease::from_bytes(&bytes).unwrap(), lease);
+
+    // Times which do not fit in the timestamp are rejected
+    let early = Lease { expires: UNIX_EPOCH - Duration::from_secs(1), ..lease };
+    assert!(early.write_to(&mut Cursor::new(Vec::new())).is_err());
+
+    // Codecs follow the endianness chosen by the parent
+    let route = Le(Route(Ipv4Addr::new(10, 0, 0, 1), 24));
+    let bytes = [1, 0, 0, 10, 24];
+    assert_eq!(route.to_bytes().unwrap(), bytes);
+    assert_eq!(Le::<Route>::from_bytes(&bytes).unwrap(), route);
+
+    // Args are borrowed on read, so they can name a field which is not `Copy`
+    let masked = Masked { key: vec![0x0f, 0x30], value: 0x12 };
+    let bytes = [2, 0x0f, 0x30, 0x2d];
+    assert_eq!(masked.to_bytes().unwrap(), bytes);
+    assert_eq!(Masked::from_bytes(&bytes).unwrap(), masked);
+
+    let mut cursor = Cursor::new(Vec::new());
+    io_self::with::unix_timestamp_millis::write_to::<LittleEndian, _>(&(UNIX_EPOCH - Duration::from_millis(2)), &mut cursor, ()).unwrap();
+    assert_eq!(cursor.get_ref(), &(-2i64).to_le_bytes());
+    cursor.set_position(0);
+    let time = io_self::with::unix_timestamp_millis::read_from::<LittleEndian, _>(&mut cursor, ()).unwrap();
+    assert_eq!(time, UNIX_EPOCH - Duration::from_millis(2));
+}
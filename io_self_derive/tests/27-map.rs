@@ -0,0 +1,88 @@
+#![allow(dead_code)]
+use io_self::{ReadSelf, WriteSelf};
+use io_self_derive::{ReadSelf, WriteSelf};
+use std::num::NonZeroU16;
+use std::time::Duration;
+
+#[derive(ReadSelf, WriteSelf, Debug, PartialEq, Copy, Clone)]
+#[io_self(try_from = u8, into = u8)]
+pub enum Kind {
+    Request = 1,
+    Response = 2,
+}
+
+impl TryFrom<u8> for Kind {
+    type Error = String;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            1 => Ok(Kind::Request),
+            2 => Ok(Kind::Response),
+            x => Err(format!("unknown kind {}", x)),
+        }
+    }
+}
+
+impl From<Kind> for u8 {
+    fn from(kind: Kind) -> Self {
+        kind as u8
+    }
+}
+
+#[derive(ReadSelf, WriteSelf, Debug, PartialEq, Clone)]
+#[io_self(endian = "big", from = "u16", into = "u16")]
+pub struct Version {
+    major: u8,
+    minor: u8,
+}
+
+impl From<u16> for Version {
+    fn from(value: u16) -> Self {
+        Version { major: (value >> 8) as u8, minor: value as u8 }
+    }
+}
+
+impl From<Version> for u16 {
+    fn from(version: Version) -> Self {
+        (version.major as u16) << 8 | version.minor as u16
+    }
+}
+
+#[derive(ReadSelf, WriteSelf, Debug, PartialEq)]
+#[io_self(endian = "little")]
+pub struct Header {
+    kind: Kind,
+    version: Version,
+    #[io_self(map = |x: u32| Duration::from_millis(x as u64), write_map = |d: &Duration| d.as_millis() as u32)]
+    timeout: Duration,
+    #[io_self(try_map = "|x: u16| NonZeroU16::try_from(x)", write_map = "|x: &NonZeroU16| x.get()")]
+    window: NonZeroU16,
+    #[io_self(map = u16::swap_bytes, write_map = |x: &u16| x.swap_bytes(), endian = "big")]
+    swapped: u16,
+}
+
+fn error_message<T: ReadSelf>(bytes: &[u8]) -> String {
+    T::from_bytes(&bytes).err().unwrap().to_string()
+}
+
+fn main() {
+    let header = Header {
+        kind: Kind::Response,
+        version: Version { major: 1, minor: 2 },
+        timeout: Duration::from_millis(1000),
+        window: NonZeroU16::new(5).unwrap(),
+        swapped: 0x0102,
+    };
+    let bytes = [2, 1, 2, 0xE8, 3, 0, 0, 5, 0, 2, 1];
+    assert_eq!(header.to_bytes().unwrap(), bytes);
+    assert_eq!(Header::from_bytes(&bytes).unwrap(), header);
+
+    // Failed conversions name where they happened
+    let mut invalid = bytes;
+    invalid[0] = 3;
+    assert_eq!(error_message::<Header>(&invalid), "invalid `Kind`: unknown kind 3");
+
+    let mut invalid = bytes;
+    invalid[7] = 0;
+    assert!(error_message::<Header>(&invalid).starts_with("invalid field `window`: "));
+}
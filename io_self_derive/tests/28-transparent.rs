@@ -0,0 +1,71 @@
+#![allow(dead_code)]
+use io_self::positional::AsyncReadCounter;
+use io_self::{AsyncReadSelf, ReadSelf, WriteSelf};
+use io_self_derive::{AsyncReadSelf, AsyncWriteSelf, ReadSelf, WriteSelf};
+
+#[derive(ReadSelf, WriteSelf, AsyncReadSelf, AsyncWriteSelf, Debug, PartialEq, Copy, Clone)]
+#[io_self(transparent)]
+pub struct Offset(u32);
+
+#[derive(ReadSelf, WriteSelf, Debug, PartialEq, Copy, Clone)]
+#[io_self(transparent)]
+pub struct Id {
+    value: u8,
+}
+
+#[derive(ReadSelf, WriteSelf, Debug, PartialEq)]
+#[io_self(transparent)]
+pub struct Wrapper<T>(T);
+
+#[derive(ReadSelf, WriteSelf, AsyncReadSelf, AsyncWriteSelf, Debug, PartialEq)]
+#[io_self(endian = "big")]
+pub struct BigTable {
+    start: Offset,
+    entries: [Offset; 2],
+}
+
+#[derive(ReadSelf, WriteSelf, Debug, PartialEq)]
+#[io_self(endian = "little")]
+pub struct LittleTable {
+    start: Offset,
+    id: Id,
+    #[io_self(length_prefix = "u8")]
+    sizes: Vec<Wrapper<u16>>,
+}
+
+// Without an endianness, only fields which implement ReadSelf can be used
+#[derive(ReadSelf, WriteSelf, Debug, PartialEq)]
+pub struct Ids {
+    first: Id,
+    second: Wrapper<Id>,
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() {
+    let table = BigTable {
+        start: Offset(0x01020304),
+        entries: [Offset(5), Offset(6)],
+    };
+    let bytes = [1, 2, 3, 4, 0, 0, 0, 5, 0, 0, 0, 6];
+    assert_eq!(table.to_bytes().unwrap(), bytes);
+    assert_eq!(BigTable::from_bytes(&bytes).unwrap(), table);
+
+    let mut reader = AsyncReadCounter::new(&bytes[..]);
+    assert_eq!(BigTable::read_from_async(&mut reader).await.unwrap(), table);
+
+    let table = LittleTable {
+        start: Offset(0x01020304),
+        id: Id { value: 7 },
+        sizes: vec![Wrapper(0x0809)],
+    };
+    let bytes = [4, 3, 2, 1, 7, 1, 9, 8];
+    assert_eq!(table.to_bytes().unwrap(), bytes);
+    assert_eq!(LittleTable::from_bytes(&bytes).unwrap(), table);
+
+    let ids = Ids {
+        first: Id { value: 1 },
+        second: Wrapper(Id { value: 2 }),
+    };
+    assert_eq!(ids.to_bytes().unwrap(), [1, 2]);
+    assert_eq!(Ids::from_bytes(&[1, 2]).unwrap(), ids);
+}
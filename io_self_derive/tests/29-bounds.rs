@@ -0,0 +1,86 @@
+#![allow(dead_code)]
+use io_self::endian::{LittleEndian, ReadSelfEndian, WriteSelfEndian};
+use io_self::{ReadSelf, WriteSelf};
+use io_self_derive::{ReadSelf, WriteSelf};
+use std::io;
+use std::marker::PhantomData;
+
+/// Implements neither `ReadSelf` nor `WriteSelf`.
+#[derive(Debug, PartialEq, Default)]
+pub struct Meters;
+
+pub trait Unit {
+    type Value;
+}
+
+impl Unit for Meters {
+    type Value = u16;
+}
+
+// Parameters which only appear in PhantomData are not bounded
+#[derive(ReadSelf, WriteSelf, Debug, PartialEq)]
+#[io_self(endian = "big")]
+pub struct Distance<U> {
+    value: u32,
+    unit: PhantomData<U>,
+}
+
+// Parameters behind a custom function are not bounded either
+#[derive(ReadSelf, WriteSelf, Debug, PartialEq)]
+pub struct Skipped<T> {
+    #[io_self(read_fn = none, write_fn = skip)]
+    value: Option<T>,
+    count: u8,
+}
+
+// Bounds can be given when the inferred ones are wrong
+#[derive(ReadSelf, WriteSelf, Debug, PartialEq)]
+#[io_self(endian = "little")]
+pub struct Measurement<U: Unit> {
+    #[io_self(bound(read = "U::Value: ReadSelfEndian<LittleEndian>", write = "U::Value: WriteSelfEndian<LittleEndian>"))]
+    value: U::Value,
+    unit: PhantomData<U>,
+}
+
+#[derive(ReadSelf, WriteSelf, Debug, PartialEq)]
+#[io_self(bound(read = "T: Default", write = ""))]
+pub struct Defaulted<T> {
+    #[io_self(read_fn = default_value, write_fn = skip)]
+    value: T,
+}
+
+fn none<T, B>(_: &mut B) -> io::Result<Option<T>> {
+    Ok(None)
+}
+
+fn default_value<T: Default, B>(_: &mut B) -> io::Result<T> {
+    Ok(T::default())
+}
+
+fn skip<T, B>(_: &T, _: &mut B) -> io::Result<()> {
+    Ok(())
+}
+
+fn main() {
+    let distance = Distance::<Meters> {
+        value: 5,
+        unit: PhantomData,
+    };
+    assert_eq!(distance.to_bytes().unwrap(), [0, 0, 0, 5]);
+    assert_eq!(Distance::<Meters>::from_bytes(&[0, 0, 0, 5]).unwrap(), distance);
+
+    let skipped = Skipped::<Meters> { value: None, count: 3 };
+    assert_eq!(skipped.to_bytes().unwrap(), [3]);
+    assert_eq!(Skipped::<Meters>::from_bytes(&[3]).unwrap(), skipped);
+
+    let measurement = Measurement::<Meters> {
+        value: 0x0102,
+        unit: PhantomData,
+    };
+    assert_eq!(measurement.to_bytes().unwrap(), [2, 1]);
+    assert_eq!(Measurement::<Meters>::from_bytes(&[2, 1]).unwrap(), measurement);
+
+    let defaulted = Defaulted { value: Meters };
+    assert!(defaulted.to_bytes().unwrap().is_empty());
+    assert_eq!(Defaulted::<Meters>::from_bytes(&[]).unwrap(), defaulted);
+}
@@ -0,0 +1,117 @@
+#![allow(dead_code)]
+use io_self::positional::ReadCounter;
+use io_self::{AsyncReadSelf, PositionAware, ReadSelf, WriteSelf};
+use io_self_derive::{AsyncReadSelf, AsyncWriteSelf, ReadSelf, WriteSelf};
+use std::io::{self, Cursor, ErrorKind, Read, Write};
+
+/// Four bytes which must match `M` when read as a big endian integer.
+#[derive(Debug, PartialEq, Default)]
+pub struct Magic<const M: u32>;
+
+impl<const M: u32> ReadSelf for Magic<M> {
+    fn read_from<B: Read + PositionAware>(buffer: &mut B) -> io::Result<Self> {
+        let mut bytes = [0u8; 4];
+        buffer.read_exact(&mut bytes)?;
+        match u32::from_be_bytes(bytes) == M {
+            true => Ok(Magic),
+            false => Err(io::Error::new(ErrorKind::InvalidData, "bad magic")),
+        }
+    }
+}
+
+impl<const M: u32> WriteSelf for Magic<M> {
+    fn write_to<B: Write + PositionAware>(&self, buffer: &mut B) -> io::Result<()> {
+        buffer.write_all(&M.to_be_bytes())
+    }
+}
+
+io_self::endian_agnostic!([const M: u32] Magic<M>);
+
+#[derive(ReadSelf, WriteSelf, Debug, PartialEq)]
+#[io_self(untagged, endian = "big")]
+pub enum Chunk {
+    Header {
+        magic: Magic<0x48445230>,
+        version: u16,
+    },
+    Data {
+        magic: Magic<0x44415441>,
+        #[io_self(length_prefix = "u8")]
+        bytes: Vec<u8>,
+    },
+    Raw([u8; 4]),
+}
+
+#[derive(ReadSelf, WriteSelf, Debug, PartialEq)]
+#[io_self(untagged)]
+pub enum Strict {
+    Header(Magic<0x48445230>),
+    Data(Magic<0x44415441>),
+}
+
+#[derive(ReadSelf, WriteSelf, Debug, PartialEq)]
+pub struct File {
+    first: Chunk,
+    second: Chunk,
+}
+
+// Variants which run out of bytes are also rewound before the next one is tried
+#[derive(ReadSelf, WriteSelf, AsyncReadSelf, AsyncWriteSelf, Debug, PartialEq)]
+#[io_self(untagged, endian = "little")]
+pub enum Number {
+    Long(u32),
+    Short(u16),
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() {
+    let header = Chunk::Header {
+        magic: Magic,
+        version: 3,
+    };
+    assert_eq!(header.to_bytes().unwrap(), b"HDR0\x00\x03");
+    assert_eq!(Chunk::from_bytes(b"HDR0\x00\x03").unwrap(), header);
+
+    let data = Chunk::Data {
+        magic: Magic,
+        bytes: vec![1, 2],
+    };
+    assert_eq!(data.to_bytes().unwrap(), b"DATA\x02\x01\x02");
+    assert_eq!(Chunk::from_bytes(b"DATA\x02\x01\x02").unwrap(), data);
+
+    // The fallback starts from the beginning of the enum again
+    assert_eq!(Chunk::from_bytes(b"ABCD").unwrap(), Chunk::Raw(*b"ABCD"));
+
+    let file = File {
+        first: Chunk::Raw(*b"HDR1"),
+        second: header,
+    };
+    let bytes = b"HDR1HDR0\x00\x03";
+    assert_eq!(file.to_bytes().unwrap(), bytes);
+    assert_eq!(File::from_bytes(bytes).unwrap(), file);
+
+    let err = Strict::from_bytes(b"ABCD").unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+    assert_eq!(
+        err.to_string(),
+        "no variant of `Strict` matched (Header: bad magic; Data: bad magic)"
+    );
+
+    // Readers which can not seek can only read the first variant, and report why it failed
+    let mut reader = ReadCounter::new(&b"DATA"[..]);
+    let err = Strict::read_from(&mut reader).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Unsupported);
+    assert_eq!(
+        err.to_string(),
+        "no variant of `Strict` matched (Header: bad magic), and the stream could not be rewound to try the others: \
+         stream does not support seeking"
+    );
+    let mut reader = ReadCounter::new(&b"HDR0"[..]);
+    assert_eq!(Strict::read_from(&mut reader).unwrap(), Strict::Header(Magic));
+
+    assert_eq!(Number::from_bytes(&[1, 2]).unwrap(), Number::Short(0x0201));
+    let mut reader = Cursor::new(&[1u8, 2][..]);
+    assert_eq!(Number::read_from_async(&mut reader).await.unwrap(), Number::Short(0x0201));
+    let mut reader = Cursor::new(&[1u8, 0, 0, 0][..]);
+    assert_eq!(Number::read_from_async(&mut reader).await.unwrap(), Number::Long(1));
+}
@@ -0,0 +1,96 @@
+#![allow(dead_code)]
+use io_self::{ReadSelf, WriteSelf};
+use io_self_derive::{ReadSelf, WriteSelf};
+use std::io::ErrorKind;
+
+#[derive(ReadSelf, WriteSelf, Debug, PartialEq)]
+#[io_self(tag = "u8", endian = "generic")]
+pub enum Payload {
+    #[io_self(tag = 1)]
+    Ping(u32),
+    #[io_self(tag = 2)]
+    Name {
+        #[io_self(length_prefix = "u8")]
+        value: String,
+    },
+    #[io_self(tag = 3)]
+    Empty,
+}
+
+// The length sits between the tag and the payload
+#[derive(ReadSelf, WriteSelf, Debug, PartialEq)]
+#[io_self(endian = "big")]
+pub struct Record {
+    kind: u8,
+    length: u16,
+    #[io_self(tag_from = "self.kind")]
+    payload: Payload,
+}
+
+#[derive(ReadSelf, WriteSelf, Debug, PartialEq)]
+#[io_self(endian = "little")]
+pub struct LittleRecord(u8, #[io_self(tag_from = self.0)] Payload);
+
+#[derive(ReadSelf, WriteSelf, Debug, PartialEq)]
+#[io_self(tag = "u8", length_prefix = "u8")]
+pub enum Command {
+    #[io_self(tag = 0x10)]
+    Reset,
+    #[io_self(tag = 0x20)]
+    Move([u8; 2]),
+}
+
+// The length prefix of an enum covers only its variant when the tag is stored elsewhere
+#[derive(ReadSelf, WriteSelf, Debug, PartialEq)]
+pub struct Message {
+    opcode: u8,
+    sequence: u8,
+    #[io_self(tag_from = opcode)]
+    command: Command,
+}
+
+fn main() {
+    let record = Record {
+        kind: 1,
+        length: 4,
+        payload: Payload::Ping(0x01020304),
+    };
+    let bytes = [1, 0, 4, 1, 2, 3, 4];
+    assert_eq!(record.to_bytes().unwrap(), bytes);
+    assert_eq!(Record::from_bytes(&bytes).unwrap(), record);
+
+    // The tag is always written from the active variant
+    let record = Record {
+        kind: 0,
+        length: 3,
+        payload: Payload::Name { value: "ab".to_string() },
+    };
+    let bytes = [2, 0, 3, 2, b'a', b'b'];
+    assert_eq!(record.to_bytes().unwrap(), bytes);
+    assert_eq!(
+        Record::from_bytes(&bytes).unwrap(),
+        Record { kind: 2, ..record }
+    );
+
+    let err = Record::from_bytes(&[9, 0, 0]).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+    assert_eq!(err.to_string(), "Invalid tag value: 9");
+
+    let record = LittleRecord(1, Payload::Ping(0x01020304));
+    let bytes = [1, 4, 3, 2, 1];
+    assert_eq!(record.to_bytes().unwrap(), bytes);
+    assert_eq!(LittleRecord::from_bytes(&bytes).unwrap(), record);
+    assert_eq!(LittleRecord::from_bytes(&[3]).unwrap(), LittleRecord(3, Payload::Empty));
+
+    let message = Message {
+        opcode: 0x20,
+        sequence: 7,
+        command: Command::Move([1, 2]),
+    };
+    let bytes = [0x20, 7, 2, 1, 2];
+    assert_eq!(message.to_bytes().unwrap(), bytes);
+    assert_eq!(Message::from_bytes(&bytes).unwrap(), message);
+
+    // The enum can still be read on its own with an inline tag
+    assert_eq!(Command::from_bytes(&[1, 0x10]).unwrap(), Command::Reset);
+}
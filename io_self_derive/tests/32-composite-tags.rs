@@ -0,0 +1,56 @@
+#![allow(dead_code)]
+use io_self::{ReadSelf, WriteSelf};
+use io_self_derive::{ReadSelf, WriteSelf};
+
+// Dispatches on a (class, code) pair
+#[derive(ReadSelf, WriteSelf, Debug, PartialEq)]
+#[io_self(tag = "(u8, u8)", endian = "big")]
+pub enum Frame {
+    #[io_self(tag = "(0x02, 0x10)")]
+    Ack(u16),
+    #[io_self(tag = (0x02, 0x11))]
+    Nack,
+    #[io_self(tag = (0x03, 0x00))]
+    Data([u8; 2]),
+}
+
+// FourCC tags with a little endian body, as in RIFF files
+#[derive(ReadSelf, WriteSelf, Debug, PartialEq)]
+#[io_self(tag = "[u8; 4]", endian = "little")]
+pub enum Chunk {
+    #[io_self(tag = "b\"fmt \"")]
+    Format { channels: u16, rate: u32 },
+    #[io_self(tag = b"data")]
+    Data(u16),
+}
+
+// The tag is big endian while the body is little endian
+#[derive(ReadSelf, WriteSelf, Debug, PartialEq)]
+#[io_self(tag = "(u16, [u8; 2])", tag_endian = "big", endian = "little")]
+pub enum Message {
+    #[io_self(tag = (0x0102, b"OK"))]
+    Ok(u16),
+    #[io_self(tag = (0x0102, b"NO"))]
+    Err(u16),
+}
+
+fn main() {
+    let bytes = [0x02, 0x10, 0x01, 0x02];
+    assert_eq!(Frame::Ack(0x0102).to_bytes().unwrap(), bytes);
+    assert_eq!(Frame::from_bytes(&bytes).unwrap(), Frame::Ack(0x0102));
+    assert_eq!(Frame::from_bytes(&[0x02, 0x11]).unwrap(), Frame::Nack);
+    assert_eq!(Frame::from_bytes(&[0x03, 0x00, 5, 6]).unwrap(), Frame::Data([5, 6]));
+
+    let err = Frame::from_bytes(&[0x02, 0x12]).unwrap_err();
+    assert_eq!(err.to_string(), "Invalid tag value: (2, 18)");
+
+    let format = Chunk::Format { channels: 2, rate: 44100 };
+    let bytes = b"fmt \x02\x00\x44\xac\x00\x00";
+    assert_eq!(format.to_bytes().unwrap(), bytes);
+    assert_eq!(Chunk::from_bytes(bytes).unwrap(), format);
+    assert_eq!(Chunk::from_bytes(b"data\x01\x00").unwrap(), Chunk::Data(1));
+
+    let bytes = [0x01, 0x02, b'N', b'O', 0x03, 0x00];
+    assert_eq!(Message::Err(3).to_bytes().unwrap(), bytes);
+    assert_eq!(Message::from_bytes(&bytes).unwrap(), Message::Err(3));
+}
@@ -0,0 +1,109 @@
+#![allow(dead_code)]
+use io_self::positional::AsyncReadCounter;
+use io_self::{AsyncReadSelf, AsyncWriteSelf, ReadSelf, WriteSelf};
+use io_self_derive::{AsyncReadSelf, AsyncWriteSelf, ReadSelf, WriteSelf};
+use std::io::Cursor;
+
+#[derive(ReadSelf, WriteSelf, AsyncReadSelf, AsyncWriteSelf, Debug, PartialEq)]
+#[io_self(tag = "u8", endian = "big")]
+pub enum Packet {
+    #[io_self(tag = 1)]
+    Big(u16),
+    // Fields still take priority over their variant
+    #[io_self(tag = 2, endian = "little")]
+    Little(u16, #[io_self(endian = "big")] u16),
+    #[io_self(tag = 3, length_prefix = "u8")]
+    Sized {
+        id: u16,
+        #[io_self(length_prefix = "u8")]
+        name: String,
+    },
+    #[io_self(tag = 4, magic = b"OK", pad_after = 2)]
+    Marked(u8),
+    #[io_self(tag = 5, validate = "count <= 3")]
+    Counted { count: u8 },
+    #[io_self(tag = 6, validate = "self.0 <= self.1")]
+    Range(u8, u8),
+}
+
+#[derive(ReadSelf, WriteSelf, Debug, PartialEq)]
+#[io_self(untagged)]
+pub enum Image {
+    #[io_self(magic = b"\x89PNG")]
+    Png(u8),
+    #[io_self(magic = b"GIF8")]
+    Gif(u8),
+}
+
+#[derive(ReadSelf, WriteSelf, Debug, PartialEq)]
+pub struct Stream {
+    first: Packet,
+    second: Packet,
+}
+
+fn error_message<T: ReadSelf + std::fmt::Debug>(bytes: &[u8]) -> String {
+    T::from_bytes(&bytes).unwrap_err().to_string()
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() {
+    let cases = [
+        (Packet::Big(0x0102), vec![1, 1, 2]),
+        (Packet::Little(0x0102, 0x0304), vec![2, 2, 1, 3, 4]),
+        (
+            Packet::Sized {
+                id: 1,
+                name: "a".to_string(),
+            },
+            vec![3, 4, 0, 1, 1, b'a'],
+        ),
+        (Packet::Marked(7), vec![4, b'O', b'K', 7, 0, 0]),
+        (Packet::Counted { count: 3 }, vec![5, 3]),
+        (Packet::Range(1, 2), vec![6, 1, 2]),
+    ];
+
+    for (packet, bytes) in cases {
+        assert_eq!(packet.to_bytes().unwrap(), bytes);
+        assert_eq!(Packet::from_bytes(&bytes).unwrap(), packet);
+
+        let mut writer = Cursor::new(Vec::new());
+        packet.write_to_async(&mut writer).await.unwrap();
+        assert_eq!(writer.into_inner(), bytes);
+
+        let mut reader = AsyncReadCounter::new(&bytes[..]);
+        assert_eq!(Packet::read_from_async(&mut reader).await.unwrap(), packet);
+    }
+
+    // Bytes within the length prefix which are not used by the variant are skipped
+    let stream = Stream::from_bytes(&[3, 5, 0, 2, 0, 0xFF, 0xFF, 5, 1]).unwrap();
+    assert_eq!(
+        stream,
+        Stream {
+            first: Packet::Sized {
+                id: 2,
+                name: String::new()
+            },
+            second: Packet::Counted { count: 1 },
+        }
+    );
+
+    assert_eq!(
+        error_message::<Packet>(&[4, b'N', b'O', 7, 0, 0]),
+        "invalid magic for variant `Packet::Marked`: expected [4F, 4B], found [4E, 4F]"
+    );
+    assert_eq!(
+        error_message::<Packet>(&[5, 4]),
+        "variant `Packet::Counted` failed validation"
+    );
+    assert_eq!(
+        error_message::<Packet>(&[6, 2, 1]),
+        "variant `Packet::Range` failed validation"
+    );
+
+    // Validation only applies when reading
+    assert_eq!(Packet::Range(2, 1).to_bytes().unwrap(), [6, 2, 1]);
+
+    assert_eq!(Image::Gif(1).to_bytes().unwrap(), b"GIF8\x01");
+    assert_eq!(Image::from_bytes(b"GIF8\x01").unwrap(), Image::Gif(1));
+    assert_eq!(Image::from_bytes(b"\x89PNG\x02").unwrap(), Image::Png(2));
+}
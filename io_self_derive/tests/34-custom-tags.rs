@@ -0,0 +1,35 @@
+#![allow(dead_code)]
+use io_self::{ReadSelf, WriteSelf};
+use io_self_derive::{ReadSelf, WriteSelf};
+
+#[derive(ReadSelf, WriteSelf, Debug, PartialEq)]
+#[io_self(tag = "u8")]
+pub enum Kind {
+    #[io_self(tag = 1)]
+    A,
+    #[io_self(tag = 2)]
+    B,
+}
+
+// Variants are matched against the constants of the tag type itself
+io_self::tag_value!(Kind);
+
+#[derive(ReadSelf, WriteSelf, Debug, PartialEq)]
+#[io_self(tag = "Kind", endian = "little")]
+pub enum Message {
+    #[io_self(tag = "Kind::A")]
+    Short(u16),
+    #[io_self(tag = "Kind::B")]
+    Long(u32),
+}
+
+fn main() {
+    let message = Message::Long(0x01020304);
+
+    let bytes = message.to_bytes().unwrap();
+    assert_eq!(&bytes[..], &[2, 4, 3, 2, 1]);
+    assert_eq!(Message::from_bytes(&bytes).unwrap(), message);
+
+    assert_eq!(Message::from_bytes(&[1, 5, 0]).unwrap(), Message::Short(5));
+    assert!(Message::from_bytes(&[3, 5, 0]).is_err());
+}
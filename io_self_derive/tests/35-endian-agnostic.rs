@@ -0,0 +1,42 @@
+#![allow(dead_code)]
+use io_self::{PositionAware, ReadSelf, WriteSelf};
+use io_self_derive::{ReadSelf, WriteSelf};
+use std::io::{self, Read, Write};
+
+// A type with hand written impls which does not depend on the byte order
+#[derive(Debug, PartialEq)]
+pub struct Manual(u8);
+
+impl ReadSelf for Manual {
+    fn read_from<B: Read + PositionAware>(buffer: &mut B) -> io::Result<Self> {
+        u8::read_from(buffer).map(|x| Manual(x ^ 0xFF))
+    }
+}
+
+impl WriteSelf for Manual {
+    fn write_to<B: Write + PositionAware>(&self, buffer: &mut B) -> io::Result<()> {
+        (self.0 ^ 0xFF).write_to(buffer)
+    }
+}
+
+io_self::endian_agnostic!(Manual);
+
+#[derive(ReadSelf, WriteSelf, Debug, PartialEq)]
+#[io_self(endian = "big")]
+pub struct Header {
+    length: u16,
+    manual: Manual,
+    pair: [Manual; 2],
+}
+
+fn main() {
+    let header = Header {
+        length: 3,
+        manual: Manual(1),
+        pair: [Manual(2), Manual(3)],
+    };
+
+    let bytes = header.to_bytes().unwrap();
+    assert_eq!(bytes, [0, 3, 0xFE, 0xFD, 0xFC]);
+    assert_eq!(Header::from_bytes(&bytes).unwrap(), header);
+}
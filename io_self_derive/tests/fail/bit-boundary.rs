@@ -0,0 +1,11 @@
+use io_self_derive::ReadSelf;
+
+#[derive(ReadSelf)]
+pub struct Foo {
+    #[io_self(bits = 4)]
+    version: u8,
+    #[io_self(bits = 3)]
+    flags: u8,
+}
+
+fn main() {}
@@ -0,0 +1,19 @@
+use io_self_derive::{ReadSelf, WriteSelf};
+
+#[derive(ReadSelf)]
+pub struct Empty {
+    #[io_self(bits = 0)]
+    unused: u8,
+    #[io_self(bits = 8)]
+    value: u8,
+}
+
+#[derive(ReadSelf, WriteSelf)]
+pub struct Wide {
+    #[io_self(bits = 9)]
+    value: u8,
+    #[io_self(bits = 7)]
+    rest: u16,
+}
+
+fn main() {}
@@ -0,0 +1,15 @@
+use io_self_derive::ReadSelf;
+
+#[derive(ReadSelf)]
+#[io_self(bound = "T ReadSelf")]
+pub struct Invalid<T> {
+    value: T,
+}
+
+#[derive(ReadSelf)]
+pub struct UnknownKey<T> {
+    #[io_self(bound(reed = "T: ReadSelf"))]
+    value: T,
+}
+
+fn main() {}
@@ -0,0 +1,11 @@
+use io_self_derive::ReadSelf;
+
+#[derive(ReadSelf)]
+#[io_self(endian = "big")]
+pub struct Foo {
+    count: u8,
+    #[io_self(args = count)]
+    items: u32,
+}
+
+fn main() {}
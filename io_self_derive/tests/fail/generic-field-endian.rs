@@ -0,0 +1,11 @@
+use io_self_derive::{ReadSelf, WriteSelf};
+
+#[derive(ReadSelf, WriteSelf)]
+#[io_self(endian = "big")]
+pub struct Foo {
+    a: u32,
+    #[io_self(endian = "generic")]
+    b: u16,
+}
+
+fn main() {}
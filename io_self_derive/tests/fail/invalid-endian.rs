@@ -0,0 +1,16 @@
+use io_self_derive::{ReadSelf, WriteSelf};
+
+#[derive(ReadSelf, WriteSelf)]
+#[io_self(endian = "middle")]
+pub struct Foo {
+    a: u32,
+}
+
+#[derive(ReadSelf)]
+#[io_self(bit_order = "msb")]
+pub struct Bar {
+    #[io_self(endian = "native")]
+    b: u16,
+}
+
+fn main() {}
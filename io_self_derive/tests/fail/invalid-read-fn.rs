@@ -0,0 +1,12 @@
+use io_self_derive::ReadSelf;
+use io_self::pointer::FilePtr;
+
+#[derive(ReadSelf)]
+pub struct Foo {
+    #[io_self(read_fn = "read::<")]
+    a: u32,
+    #[io_self(offset_base = "a +")]
+    b: FilePtr<u32, u32>,
+}
+
+fn main() {}
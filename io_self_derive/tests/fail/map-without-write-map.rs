@@ -0,0 +1,11 @@
+use io_self_derive::{ReadSelf, WriteSelf};
+use std::time::Duration;
+
+#[derive(ReadSelf, WriteSelf)]
+#[io_self(endian = "big")]
+pub struct Foo {
+    #[io_self(map = |x: u32| Duration::from_secs(x as u64))]
+    timeout: Duration,
+}
+
+fn main() {}
@@ -0,0 +1,20 @@
+use io_self::{PositionAware, ReadSelf};
+use io_self_derive::ReadSelf;
+use std::io::{self, Read};
+
+pub struct Manual(u8);
+
+impl ReadSelf for Manual {
+    fn read_from<B: Read + PositionAware>(buffer: &mut B) -> io::Result<Self> {
+        u8::read_from(buffer).map(Manual)
+    }
+}
+
+#[derive(ReadSelf)]
+#[io_self(endian = "big")]
+pub struct Header {
+    length: u16,
+    manual: Manual,
+}
+
+fn main() {}
@@ -0,0 +1,17 @@
+use io_self_derive::ReadSelf;
+
+#[derive(ReadSelf)]
+pub enum Untagged {
+    A,
+    B,
+}
+
+#[derive(ReadSelf)]
+#[io_self(tag = "u8")]
+pub enum MissingVariantTag {
+    #[io_self(tag = "1")]
+    A,
+    B,
+}
+
+fn main() {}
@@ -0,0 +1,13 @@
+use io_self_derive::ReadSelf;
+
+fn read_flag<B: std::io::Read>(_: &mut B) -> std::io::Result<bool> {
+    Ok(true)
+}
+
+#[derive(ReadSelf)]
+pub struct Bar {
+    #[io_self(read_fn = read_flag, read_with = read_flag)]
+    flag: bool,
+}
+
+fn main() {}
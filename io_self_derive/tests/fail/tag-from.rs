@@ -0,0 +1,24 @@
+use io_self_derive::ReadSelf;
+
+#[derive(ReadSelf)]
+#[io_self(tag = "u8")]
+pub enum Payload {
+    #[io_self(tag = 1)]
+    Empty,
+}
+
+#[derive(ReadSelf)]
+pub struct LaterTag {
+    #[io_self(tag_from = self.kind)]
+    payload: Payload,
+    kind: u8,
+}
+
+#[derive(ReadSelf)]
+pub struct NotAField {
+    kind: u8,
+    #[io_self(tag_from = kind + 1)]
+    payload: Payload,
+}
+
+fn main() {}
@@ -0,0 +1,13 @@
+use io_self_derive::ReadSelf;
+
+#[derive(ReadSelf)]
+pub struct Foo {
+    #[io_self(encoding = "latin1", length_prefix = "u8", null_terminated)]
+    name: String,
+    #[io_self(encoding = "ebcdic", fixed_size = 4)]
+    code: String,
+    #[io_self(length_prefix = "u8", max_len = 16)]
+    note: String,
+}
+
+fn main() {}
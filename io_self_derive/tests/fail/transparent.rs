@@ -0,0 +1,15 @@
+use io_self_derive::ReadSelf;
+
+#[derive(ReadSelf)]
+#[io_self(transparent)]
+pub struct Pair(u8, u8);
+
+#[derive(ReadSelf)]
+#[io_self(transparent, endian = "big")]
+pub struct Offset(u32);
+
+#[derive(ReadSelf)]
+#[io_self(transparent)]
+pub struct Name(#[io_self(length_prefix = "u8")] Vec<u8>);
+
+fn main() {}
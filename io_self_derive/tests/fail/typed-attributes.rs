@@ -0,0 +1,17 @@
+use io_self_derive::ReadSelf;
+
+mod codec {
+    pub fn read_flag<B: std::io::Read>(_: &mut B) -> std::io::Result<bool> {
+        Ok(true)
+    }
+}
+
+#[derive(ReadSelf)]
+pub struct Foo {
+    #[io_self(read_with = codec::read_flga)]
+    flag: bool,
+    #[io_self(length_prefix = u17)]
+    items: Vec<u8>,
+}
+
+fn main() {}
@@ -0,0 +1,17 @@
+use io_self_derive::{ReadSelf, WriteSelf};
+
+#[derive(ReadSelf)]
+pub struct Foo {
+    #[io_self(length_prefix = "u8")]
+    name: String,
+    rest: String,
+}
+
+#[derive(WriteSelf)]
+pub struct Bar {
+    #[io_self(length_prefix = "u8")]
+    name: String,
+    rest: String,
+}
+
+fn main() {}
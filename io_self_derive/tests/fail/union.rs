@@ -0,0 +1,9 @@
+use io_self_derive::ReadSelf;
+
+#[derive(ReadSelf)]
+pub union Foo {
+    a: u32,
+    b: f32,
+}
+
+fn main() {}
@@ -0,0 +1,15 @@
+use io_self_derive::ReadSelf;
+
+#[derive(ReadSelf)]
+#[io_self(endain = "big")]
+pub struct Foo {
+    a: u32,
+}
+
+#[derive(ReadSelf)]
+pub struct Bar {
+    #[io_self(lenght_prefix = "u16")]
+    items: Vec<u8>,
+}
+
+fn main() {}
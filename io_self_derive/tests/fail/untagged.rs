@@ -0,0 +1,17 @@
+use io_self_derive::ReadSelf;
+
+#[derive(ReadSelf)]
+#[io_self(untagged, tag = "u8")]
+pub enum WithTag {
+    A(u8),
+}
+
+#[derive(ReadSelf)]
+#[io_self(untagged)]
+pub enum VariantTag {
+    #[io_self(tag = 1)]
+    A(u8),
+    B(u16),
+}
+
+fn main() {}
@@ -0,0 +1,16 @@
+use io_self_derive::ReadSelf;
+
+#[derive(ReadSelf)]
+#[io_self(endian = "big")]
+pub struct Foo {
+    count: u16,
+    items: Vec<u16>,
+}
+
+#[derive(ReadSelf)]
+pub struct Bar {
+    #[io_self(until_eof, length_prefix = "u8")]
+    items: Vec<u8>,
+}
+
+fn main() {}
@@ -0,0 +1,17 @@
+use io_self_derive::ReadSelf;
+
+#[derive(ReadSelf)]
+#[io_self(tag = "u8", endian = "big")]
+pub enum GenericVariant {
+    #[io_self(tag = 1, endian = "generic")]
+    A(u16),
+}
+
+#[derive(ReadSelf)]
+#[io_self(tag = "u8")]
+pub enum UnknownKey {
+    #[io_self(tag = 1, pad_before = 2)]
+    A(u16),
+}
+
+fn main() {}
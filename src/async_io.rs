@@ -0,0 +1,227 @@
+//! Asynchronous counterparts to `ReadSelf` and `WriteSelf` built on top of tokio's `AsyncRead` and
+//! `AsyncWrite`. These are only available with the `tokio` feature enabled.
+//!
+//! The `AsyncReadSelf` and `AsyncWriteSelf` derives use the same attributes as the blocking ones,
+//! except for these, which are reported as compile errors:
+//!
+//! - `read_fn`, `read_with`, `write_fn` and `write_with`, since the functions are blocking
+//! - `with`, since codecs are blocking
+//! - `encoding`, `null_terminated` and `fixed_size` on string fields
+//! - `tag_from`
+//! - `until_eof` when reading
+//! - `follow` when reading, since `FilePtr` has no asynchronous impls
+use crate::PositionAware;
+use std::future::Future;
+use std::io;
+use std::marker::PhantomData;
+use std::mem::MaybeUninit;
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// The asynchronous equivalent of `ReadSelf`. The returned future is required to be `Send` so
+/// decoding can be performed from within a spawned task. See the module documentation for the
+/// attributes which the derive does not support.
+pub trait AsyncReadSelf: Sized + Send {
+    fn read_from_async<B>(buffer: &mut B) -> impl Future<Output = io::Result<Self>> + Send
+    where
+        B: AsyncRead + PositionAware + Unpin + Send;
+}
+
+impl AsyncReadSelf for u8 {
+    #[inline(always)]
+    async fn read_from_async<B>(buffer: &mut B) -> io::Result<Self>
+    where
+        B: AsyncRead + PositionAware + Unpin + Send,
+    {
+        buffer.read_u8().await
+    }
+}
+
+impl AsyncReadSelf for i8 {
+    #[inline(always)]
+    async fn read_from_async<B>(buffer: &mut B) -> io::Result<Self>
+    where
+        B: AsyncRead + PositionAware + Unpin + Send,
+    {
+        buffer.read_i8().await
+    }
+}
+
+impl<T: ?Sized + Send> AsyncReadSelf for PhantomData<T> {
+    #[inline(always)]
+    async fn read_from_async<B>(_: &mut B) -> io::Result<Self>
+    where
+        B: AsyncRead + PositionAware + Unpin + Send,
+    {
+        Ok(PhantomData)
+    }
+}
+
+impl AsyncReadSelf for () {
+    #[inline(always)]
+    async fn read_from_async<B>(_: &mut B) -> io::Result<Self>
+    where
+        B: AsyncRead + PositionAware + Unpin + Send,
+    {
+        Ok(())
+    }
+}
+
+impl<T: AsyncReadSelf> AsyncReadSelf for Box<T> {
+    #[inline(always)]
+    async fn read_from_async<B>(buffer: &mut B) -> io::Result<Self>
+    where
+        B: AsyncRead + PositionAware + Unpin + Send,
+    {
+        Ok(Box::new(T::read_from_async(buffer).await?))
+    }
+}
+
+impl<T: AsyncReadSelf + Sync> AsyncReadSelf for Arc<T> {
+    #[inline(always)]
+    async fn read_from_async<B>(buffer: &mut B) -> io::Result<Self>
+    where
+        B: AsyncRead + PositionAware + Unpin + Send,
+    {
+        Ok(Arc::new(T::read_from_async(buffer).await?))
+    }
+}
+
+impl<T: AsyncReadSelf, const N: usize> AsyncReadSelf for [T; N] {
+    #[inline(always)]
+    async fn read_from_async<B>(buffer: &mut B) -> io::Result<Self>
+    where
+        B: AsyncRead + PositionAware + Unpin + Send,
+    {
+        let mut array = unsafe { MaybeUninit::<[MaybeUninit<T>; N]>::uninit().assume_init() };
+
+        for item in array.iter_mut().take(N) {
+            item.write(T::read_from_async(buffer).await?);
+        }
+
+        unsafe { Ok((&array as *const _ as *const [T; N]).read()) }
+    }
+}
+
+/// The asynchronous equivalent of `WriteSelf`. See the module documentation for the attributes
+/// which the derive does not support.
+pub trait AsyncWriteSelf: Sized + Sync {
+    fn write_to_async<B>(&self, buffer: &mut B) -> impl Future<Output = io::Result<()>> + Send
+    where
+        B: AsyncWrite + PositionAware + Unpin + Send;
+}
+
+impl AsyncWriteSelf for u8 {
+    #[inline(always)]
+    async fn write_to_async<B>(&self, buffer: &mut B) -> io::Result<()>
+    where
+        B: AsyncWrite + PositionAware + Unpin + Send,
+    {
+        buffer.write_u8(*self).await
+    }
+}
+
+impl AsyncWriteSelf for i8 {
+    #[inline(always)]
+    async fn write_to_async<B>(&self, buffer: &mut B) -> io::Result<()>
+    where
+        B: AsyncWrite + PositionAware + Unpin + Send,
+    {
+        buffer.write_i8(*self).await
+    }
+}
+
+impl<T: ?Sized + Sync> AsyncWriteSelf for PhantomData<T> {
+    #[inline(always)]
+    async fn write_to_async<B>(&self, _: &mut B) -> io::Result<()>
+    where
+        B: AsyncWrite + PositionAware + Unpin + Send,
+    {
+        Ok(())
+    }
+}
+
+impl AsyncWriteSelf for () {
+    #[inline(always)]
+    async fn write_to_async<B>(&self, _: &mut B) -> io::Result<()>
+    where
+        B: AsyncWrite + PositionAware + Unpin + Send,
+    {
+        Ok(())
+    }
+}
+
+impl<T: AsyncWriteSelf + Send> AsyncWriteSelf for Box<T> {
+    #[inline(always)]
+    async fn write_to_async<B>(&self, buffer: &mut B) -> io::Result<()>
+    where
+        B: AsyncWrite + PositionAware + Unpin + Send,
+    {
+        T::write_to_async(self, buffer).await
+    }
+}
+
+impl<T: AsyncWriteSelf + Send> AsyncWriteSelf for Arc<T> {
+    #[inline(always)]
+    async fn write_to_async<B>(&self, buffer: &mut B) -> io::Result<()>
+    where
+        B: AsyncWrite + PositionAware + Unpin + Send,
+    {
+        T::write_to_async(self, buffer).await
+    }
+}
+
+impl<T: AsyncWriteSelf, const N: usize> AsyncWriteSelf for [T; N] {
+    #[inline(always)]
+    async fn write_to_async<B>(&self, buffer: &mut B) -> io::Result<()>
+    where
+        B: AsyncWrite + PositionAware + Unpin + Send,
+    {
+        for item in self {
+            item.write_to_async(buffer).await?;
+        }
+        Ok(())
+    }
+}
+
+#[doc(hidden)]
+macro_rules! impl_async_tuple {
+    ($($generic:ident)*) => {
+        impl<$($generic: AsyncReadSelf),*> AsyncReadSelf for ($($generic),*) {
+            #[inline(always)]
+            async fn read_from_async<Buf>(buffer: &mut Buf) -> io::Result<Self>
+            where
+                Buf: AsyncRead + PositionAware + Unpin + Send,
+            {
+                Ok(($(<$generic as AsyncReadSelf>::read_from_async(buffer).await?),*))
+            }
+        }
+
+        impl<$($generic: AsyncWriteSelf),*> AsyncWriteSelf for ($($generic),*) {
+            #[inline(always)]
+            async fn write_to_async<Buf>(&self, buffer: &mut Buf) -> io::Result<()>
+            where
+                Buf: AsyncWrite + PositionAware + Unpin + Send,
+            {
+                #[allow(non_snake_case)]
+                let ($($generic),*) = self;
+                $(<$generic as AsyncWriteSelf>::write_to_async($generic, buffer).await?;)*
+                Ok(())
+            }
+        }
+    };
+}
+
+impl_async_tuple! {A B}
+impl_async_tuple! {A B C}
+impl_async_tuple! {A B C D}
+impl_async_tuple! {A B C D E}
+impl_async_tuple! {A B C D E F}
+impl_async_tuple! {A B C D E F G}
+impl_async_tuple! {A B C D E F G H}
+impl_async_tuple! {A B C D E F G H I}
+impl_async_tuple! {A B C D E F G H I J}
+impl_async_tuple! {A B C D E F G H I J K}
+impl_async_tuple! {A B C D E F G H I J K L}
+impl_async_tuple! {A B C D E F G H I J K L M}
+impl_async_tuple! {A B C D E F G H I J K L M N}
@@ -0,0 +1,283 @@
+//! Reading and writing values which do not occupy whole bytes. Bits are gathered into groups which
+//! must start and end on a byte boundary, such as the version and header length nibbles at the
+//! start of an IPv4 header. The derive macros use this for consecutive fields with a `bits`
+//! attribute.
+use std::io::{self, Error, ErrorKind, Read, Write};
+
+#[cfg(feature = "tokio")]
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// The order bits are taken from each byte.
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum BitOrder {
+    /// The first value occupies the most significant bits of the first byte, and each value is
+    /// stored most significant bit first. This is the order used by most network protocols.
+    #[default]
+    MsbFirst,
+    /// The first value occupies the least significant bits of the first byte, and each value is
+    /// stored least significant bit first (Ex: DEFLATE).
+    LsbFirst,
+}
+
+/// Types which can be stored in a field of an arbitrary number of bits.
+pub trait BitField: Sized {
+    /// The largest number of bits this type can be read from.
+    const MAX_BITS: u32;
+
+    /// Convert the lowest `count` bits of `bits` into a value.
+    fn from_bits(bits: u64, count: u32) -> Self;
+
+    /// Convert the value to its lowest `count` bits, or return `None` if it does not fit.
+    fn to_bits(&self, count: u32) -> Option<u64>;
+}
+
+macro_rules! impl_bit_field {
+    (unsigned $($name:ty),+) => {
+        $(impl BitField for $name {
+            const MAX_BITS: u32 = <$name>::BITS;
+
+            fn from_bits(bits: u64, _count: u32) -> Self {
+                bits as $name
+            }
+
+            fn to_bits(&self, count: u32) -> Option<u64> {
+                let bits = *self as u64;
+                match count {
+                    64 => Some(bits),
+                    _ if bits >> count == 0 => Some(bits),
+                    _ => None,
+                }
+            }
+        })+
+    };
+    (signed $($name:ty),+) => {
+        $(impl BitField for $name {
+            const MAX_BITS: u32 = <$name>::BITS;
+
+            fn from_bits(bits: u64, count: u32) -> Self {
+                // Shift the sign bit to the top before shifting back to sign extend
+                let unused = 64 - count;
+                ((bits << unused) as i64 >> unused) as $name
+            }
+
+            fn to_bits(&self, count: u32) -> Option<u64> {
+                let value = *self as i64;
+                let unused = 64 - count;
+                match (value << unused) >> unused == value {
+                    true => Some(value as u64 & (u64::MAX >> unused)),
+                    false => None,
+                }
+            }
+        })+
+    };
+}
+
+impl_bit_field!(unsigned u8, u16, u32, u64);
+impl_bit_field!(signed i8, i16, i32, i64);
+
+impl BitField for bool {
+    const MAX_BITS: u32 = 1;
+
+    fn from_bits(bits: u64, _count: u32) -> Self {
+        bits != 0
+    }
+
+    fn to_bits(&self, _count: u32) -> Option<u64> {
+        Some(*self as u64)
+    }
+}
+
+fn check_count<T: BitField>(count: u32) -> io::Result<()> {
+    if count == 0 || count > T::MAX_BITS {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!(
+                "a field of {} bits does not fit in {}",
+                count,
+                std::any::type_name::<T>()
+            ),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Tracks the bit position within a group of bit fields while reading.
+#[derive(Debug, Copy, Clone)]
+pub struct BitReader {
+    order: BitOrder,
+    current: u8,
+    remaining: u32,
+}
+
+impl BitReader {
+    pub fn new(order: BitOrder) -> Self {
+        BitReader {
+            order,
+            current: 0,
+            remaining: 0,
+        }
+    }
+
+    /// Add the next bit of the current byte to `value`. `remaining` must be non-zero.
+    fn take_bit(&mut self, value: u64, idx: u32) -> u64 {
+        self.remaining -= 1;
+        match self.order {
+            BitOrder::MsbFirst => (value << 1) | ((self.current >> self.remaining) & 1) as u64,
+            BitOrder::LsbFirst => {
+                value | (((self.current >> (7 - self.remaining)) & 1) as u64) << idx
+            }
+        }
+    }
+
+    /// Read a value from the next `count` bits, reading more bytes from `buffer` as needed.
+    pub fn read<T: BitField, B: Read>(&mut self, buffer: &mut B, count: u32) -> io::Result<T> {
+        check_count::<T>(count)?;
+
+        let mut value = 0;
+        for idx in 0..count {
+            if self.remaining == 0 {
+                let mut byte = [0u8];
+                buffer.read_exact(&mut byte)?;
+                self.current = byte[0];
+                self.remaining = 8;
+            }
+            value = self.take_bit(value, idx);
+        }
+
+        Ok(T::from_bits(value, count))
+    }
+
+    #[cfg(feature = "tokio")]
+    pub async fn read_async<T, B>(&mut self, buffer: &mut B, count: u32) -> io::Result<T>
+    where
+        T: BitField,
+        B: AsyncRead + Unpin,
+    {
+        check_count::<T>(count)?;
+
+        let mut value = 0;
+        for idx in 0..count {
+            if self.remaining == 0 {
+                self.current = buffer.read_u8().await?;
+                self.remaining = 8;
+            }
+            value = self.take_bit(value, idx);
+        }
+
+        Ok(T::from_bits(value, count))
+    }
+
+    /// Check that the group ended on a byte boundary.
+    pub fn finish(self) -> io::Result<()> {
+        match self.remaining {
+            0 => Ok(()),
+            x => Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("bit group ended with {} unread bits", x),
+            )),
+        }
+    }
+}
+
+/// Tracks the bit position within a group of bit fields while writing. Each byte is written once
+/// all 8 of its bits have been filled.
+#[derive(Debug, Copy, Clone)]
+pub struct BitWriter {
+    order: BitOrder,
+    current: u8,
+    filled: u32,
+}
+
+impl BitWriter {
+    pub fn new(order: BitOrder) -> Self {
+        BitWriter {
+            order,
+            current: 0,
+            filled: 0,
+        }
+    }
+
+    /// Add the next bit to the current byte, returning the byte once it is full.
+    fn push_bit(&mut self, bit: u8) -> Option<u8> {
+        match self.order {
+            BitOrder::MsbFirst => self.current |= bit << (7 - self.filled),
+            BitOrder::LsbFirst => self.current |= bit << self.filled,
+        }
+        self.filled += 1;
+
+        if self.filled < 8 {
+            return None;
+        }
+
+        self.filled = 0;
+        Some(std::mem::take(&mut self.current))
+    }
+
+    fn bits_of<T: BitField>(
+        order: BitOrder,
+        value: &T,
+        count: u32,
+    ) -> io::Result<impl Iterator<Item = u8>> {
+        check_count::<T>(count)?;
+        let bits = value.to_bits(count).ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("value does not fit in {} bits", count),
+            )
+        })?;
+
+        Ok((0..count).map(move |idx| match order {
+            BitOrder::MsbFirst => (bits >> (count - idx - 1)) as u8 & 1,
+            BitOrder::LsbFirst => (bits >> idx) as u8 & 1,
+        }))
+    }
+
+    /// Write `value` to the next `count` bits. An error is returned if the value does not fit.
+    pub fn write<T: BitField, B: Write>(
+        &mut self,
+        buffer: &mut B,
+        value: &T,
+        count: u32,
+    ) -> io::Result<()> {
+        for bit in Self::bits_of(self.order, value, count)? {
+            if let Some(byte) = self.push_bit(bit) {
+                buffer.write_all(&[byte])?;
+            }
+        }
+
+        Ok(())
+    }
+
+    #[cfg(feature = "tokio")]
+    pub async fn write_async<T, B>(
+        &mut self,
+        buffer: &mut B,
+        value: &T,
+        count: u32,
+    ) -> io::Result<()>
+    where
+        T: BitField,
+        B: AsyncWrite + Unpin,
+    {
+        for bit in Self::bits_of(self.order, value, count)? {
+            if let Some(byte) = self.push_bit(bit) {
+                buffer.write_all(&[byte]).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check that the group ended on a byte boundary. Since incomplete bytes are never written, a
+    /// group which does not fill its final byte is an error.
+    pub fn finish(self) -> io::Result<()> {
+        match self.filled {
+            0 => Ok(()),
+            x => Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("bit group ended with {} bits of an incomplete byte", x),
+            )),
+        }
+    }
+}
@@ -0,0 +1,202 @@
+//! Integration with `tokio_util::codec` for length delimited streams of `ReadSelf`/`WriteSelf`
+//! types. This is only available with the `codec` feature enabled.
+use crate::positional::WriteCounter;
+use crate::{PositionAware, ReadSelf, WriteSelf};
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+use bytes::{Buf, BufMut, BytesMut};
+use std::io::{self, Cursor, Error, ErrorKind};
+use std::marker::PhantomData;
+use tokio_util::codec::{Decoder, Encoder};
+
+/// The integer type used to store the length of each frame.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LengthPrefix {
+    U8,
+    U16,
+    U32,
+    U64,
+}
+
+impl LengthPrefix {
+    fn width(self) -> usize {
+        match self {
+            LengthPrefix::U8 => 1,
+            LengthPrefix::U16 => 2,
+            LengthPrefix::U32 => 4,
+            LengthPrefix::U64 => 8,
+        }
+    }
+
+    fn max_value(self) -> u64 {
+        match self {
+            LengthPrefix::U8 => u8::MAX as u64,
+            LengthPrefix::U16 => u16::MAX as u64,
+            LengthPrefix::U32 => u32::MAX as u64,
+            LengthPrefix::U64 => u64::MAX,
+        }
+    }
+
+    fn read<O: ByteOrder>(self, bytes: &[u8]) -> u64 {
+        match self {
+            LengthPrefix::U8 => bytes[0] as u64,
+            LengthPrefix::U16 => O::read_u16(bytes) as u64,
+            LengthPrefix::U32 => O::read_u32(bytes) as u64,
+            LengthPrefix::U64 => O::read_u64(bytes),
+        }
+    }
+
+    fn write<O: ByteOrder>(self, bytes: &mut [u8], value: u64) {
+        match self {
+            LengthPrefix::U8 => bytes[0] = value as u8,
+            LengthPrefix::U16 => O::write_u16(bytes, value as u16),
+            LengthPrefix::U32 => O::write_u32(bytes, value as u32),
+            LengthPrefix::U64 => O::write_u64(bytes, value),
+        }
+    }
+}
+
+/// A codec for streams where each value is sent as a frame prefixed by its length in bytes. By
+/// default the length is a big endian `u32` and frames are limited to 8MB.
+///
+/// When decoding, each frame is split off of the `BytesMut` and read in place. A frame must be
+/// consumed exactly by the value it holds, otherwise an `InvalidData` error is returned. Frames
+/// longer than the maximum frame length are rejected before any of their body is buffered.
+#[derive(Debug, Copy, Clone)]
+pub struct IoSelfCodec<T> {
+    length_prefix: LengthPrefix,
+    little_endian: bool,
+    max_frame_length: usize,
+    _phantom: PhantomData<fn() -> T>,
+}
+
+impl<T> Default for IoSelfCodec<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> IoSelfCodec<T> {
+    pub fn new() -> Self {
+        IoSelfCodec {
+            length_prefix: LengthPrefix::U32,
+            little_endian: false,
+            max_frame_length: 8 * 1024 * 1024,
+            _phantom: PhantomData,
+        }
+    }
+
+    pub fn length_prefix(mut self, length_prefix: LengthPrefix) -> Self {
+        self.length_prefix = length_prefix;
+        self
+    }
+
+    pub fn big_endian(mut self) -> Self {
+        self.little_endian = false;
+        self
+    }
+
+    pub fn little_endian(mut self) -> Self {
+        self.little_endian = true;
+        self
+    }
+
+    /// Set the maximum length of a frame body, not including the length prefix.
+    pub fn max_frame_length(mut self, max_frame_length: usize) -> Self {
+        self.max_frame_length = max_frame_length;
+        self
+    }
+
+    fn read_prefix(&self, bytes: &[u8]) -> u64 {
+        match self.little_endian {
+            true => self.length_prefix.read::<LittleEndian>(bytes),
+            false => self.length_prefix.read::<BigEndian>(bytes),
+        }
+    }
+
+    fn write_prefix(&self, bytes: &mut [u8], value: u64) {
+        match self.little_endian {
+            true => self.length_prefix.write::<LittleEndian>(bytes, value),
+            false => self.length_prefix.write::<BigEndian>(bytes, value),
+        }
+    }
+
+    fn check_length(&self, length: u64) -> io::Result<usize> {
+        let max_length = (self.max_frame_length as u64).min(self.length_prefix.max_value());
+        if length > max_length {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "frame of {} bytes exceeds maximum frame length of {} bytes",
+                    length, max_length
+                ),
+            ));
+        }
+
+        Ok(length as usize)
+    }
+}
+
+impl<T: ReadSelf> Decoder for IoSelfCodec<T> {
+    type Item = T;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> io::Result<Option<T>> {
+        let prefix_width = self.length_prefix.width();
+        if src.len() < prefix_width {
+            return Ok(None);
+        }
+
+        let length = self.check_length(self.read_prefix(&src[..prefix_width]))?;
+        if src.len() < prefix_width + length {
+            src.reserve(prefix_width + length - src.len());
+            return Ok(None);
+        }
+
+        src.advance(prefix_width);
+        let frame = src.split_to(length).freeze();
+
+        let mut cursor = Cursor::new(&frame[..]);
+        let value = T::read_from(&mut cursor)?;
+
+        if cursor.position() != length as u64 {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "frame contained {} trailing bytes",
+                    length as u64 - cursor.position()
+                ),
+            ));
+        }
+
+        Ok(Some(value))
+    }
+}
+
+impl<T: WriteSelf> Encoder<T> for IoSelfCodec<T> {
+    type Error = io::Error;
+
+    fn encode(&mut self, item: T, dst: &mut BytesMut) -> io::Result<()> {
+        let prefix_width = self.length_prefix.width();
+        let start = dst.len();
+
+        // Write a placeholder for the length, then fill it in once the body has been written
+        dst.put_bytes(0, prefix_width);
+        let mut writer = WriteCounter::new(dst.writer());
+        let length = item
+            .write_to(&mut writer)
+            .and_then(|_| writer.position())
+            .and_then(|length| self.check_length(length));
+
+        match length {
+            Ok(length) => {
+                self.write_prefix(&mut dst[start..start + prefix_width], length as u64);
+                Ok(())
+            }
+            Err(e) => {
+                // Do not leave a partial frame behind in the output
+                dst.truncate(start);
+                Err(e)
+            }
+        }
+    }
+}
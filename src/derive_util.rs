@@ -1,22 +1,35 @@
 //! Helper traits to help with derive macos
-use crate::{AbortingFromIterator, PositionAware, ReadSelf, WriteSelf};
-use byteorder::{ByteOrder, ReadBytesExt, WriteBytesExt};
+use crate::grammar::{utf8_at, I24, U24, U40, U48, U56};
+use crate::helper::{read_unless_eof, read_until_eof, EofTracker};
+use crate::{AbortingFromIterator, PositionAware};
+use byteorder::{ReadBytesExt, WriteBytesExt};
+use std::fmt::Display;
 use std::io::{self, Error, ErrorKind, Read, Write};
 
 #[doc(hidden)]
-pub use byteorder::{BigEndian, LittleEndian};
+pub use byteorder::{BigEndian, ByteOrder, LittleEndian};
 
+#[cfg(feature = "tokio")]
+use std::future::Future;
+#[cfg(feature = "tokio")]
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+#[cfg(feature = "tokio")]
+#[doc(hidden)]
+pub use tokio;
+
+/// Reads a value with the byte order `O`. Types which do not depend on the byte order implement
+/// this for any `O` by reading through `ReadSelf`. Derived types get this automatically, but a
+/// type implementing `ReadSelf` by hand needs `io_self::endian_agnostic!(MyType)` to be used as a
+/// field of a struct with an endianness.
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` can not be read with a byte order",
+    note = "types implementing `ReadSelf` by hand can use `io_self::endian_agnostic!({Self})`"
+)]
 pub trait ReadSelfEndian<O>: Sized {
     fn read_from<B: Read + PositionAware>(buffer: &mut B) -> io::Result<Self>;
 }
 
-impl<O, T: ReadSelf> ReadSelfEndian<O> for T {
-    #[inline(always)]
-    fn read_from<B: Read + PositionAware>(buffer: &mut B) -> io::Result<Self> {
-        <T as ReadSelf>::read_from(buffer)
-    }
-}
-
 /// Utility function to help allow the compiler to infer types.
 /// TODO: Should this instead be inlined by the derive macro?
 #[inline(always)]
@@ -26,10 +39,7 @@ where
     A: FromIterator<T>,
     F: Fn(&mut B) -> io::Result<T>,
 {
-    (0..len)
-        .into_iter()
-        .map(|_| parser(buffer))
-        .aborting_from_iter()
+    (0..len).map(|_| parser(buffer)).aborting_from_iter()
 }
 
 /// Utility function to help allow the compiler to infer types.
@@ -60,7 +70,7 @@ where
 
     let length_prefix = match P::try_from(count) {
         Ok(v) => v,
-        Err(e) => return Err(Error::new(ErrorKind::Other, e)),
+        Err(e) => return Err(Error::other(e)),
     };
     prefix_writer(&length_prefix, buffer)?;
 
@@ -77,15 +87,251 @@ where
     Ok(())
 }
 
+/// Read a UTF-8 string where the length prefix counts bytes.
+#[inline(always)]
+pub fn read_string_with_length<B: Read + PositionAware>(
+    buffer: &mut B,
+    len: usize,
+) -> io::Result<String> {
+    let start = buffer.position()?;
+
+    // Avoid trusting the length with a large allocation before the bytes have actually been read
+    let mut bytes = Vec::new();
+    buffer.take(len as u64).read_to_end(&mut bytes)?;
+    if bytes.len() != len {
+        return Err(ErrorKind::UnexpectedEof.into());
+    }
+
+    utf8_at(bytes, start)
+}
+
+/// Write a string preceded by its length in bytes.
+#[inline(always)]
+pub fn write_str_with_prefix<P, B, G>(
+    value: &str,
+    buffer: &mut B,
+    prefix_writer: G,
+) -> io::Result<()>
+where
+    P: TryFrom<usize>,
+    <P as TryFrom<usize>>::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+    B: Write + PositionAware,
+    G: FnOnce(&P, &mut B) -> io::Result<()>,
+{
+    let length_prefix = P::try_from(value.len()).map_err(Error::other)?;
+    prefix_writer(&length_prefix, buffer)?;
+    buffer.write_all(value.as_bytes())
+}
+
+/// Reads a field marked `until_eof`. A `Vec` reads items until the stream ends cleanly between two
+/// of them, while an `Option` is `None` if the stream has already ended. See `ReadSelf::iter_from`
+/// and `ReadSelf::try_read_from`.
+pub trait ReadUntilEof<T>: Sized {
+    fn read_until_eof<B, F>(buffer: &mut B, reader: F) -> io::Result<Self>
+    where
+        B: Read + PositionAware,
+        F: FnMut(&mut EofTracker<'_, B>) -> io::Result<T>;
+}
+
+impl<T> ReadUntilEof<T> for Vec<T> {
+    #[inline(always)]
+    fn read_until_eof<B, F>(buffer: &mut B, reader: F) -> io::Result<Self>
+    where
+        B: Read + PositionAware,
+        F: FnMut(&mut EofTracker<'_, B>) -> io::Result<T>,
+    {
+        read_until_eof(buffer, reader)
+    }
+}
+
+impl<T> ReadUntilEof<T> for Option<T> {
+    #[inline(always)]
+    fn read_until_eof<B, F>(buffer: &mut B, reader: F) -> io::Result<Self>
+    where
+        B: Read + PositionAware,
+        F: FnMut(&mut EofTracker<'_, B>) -> io::Result<T>,
+    {
+        read_unless_eof(buffer, reader)
+    }
+}
+
+/// Applies the `map` of a field to the value read from the stream. Passing both to a function lets
+/// the compiler infer the type on the wire from the argument of `map`.
+#[inline(always)]
+pub fn map_value<W, T, F: FnOnce(W) -> T>(wire: W, map: F) -> T {
+    map(wire)
+}
+
+/// Applies the `try_map` of a field, reporting a failed conversion as `InvalidData`.
+#[inline(always)]
+pub fn try_map_value<W, T, E, F>(wire: W, map: F, context: &str) -> io::Result<T>
+where
+    E: Display,
+    F: FnOnce(W) -> Result<T, E>,
+{
+    map(wire).map_err(|err| invalid_conversion(context, err))
+}
+
+/// The error for a value which could not be converted, naming the field or type it was for.
+pub fn invalid_conversion<E: Display>(context: &str, err: E) -> Error {
+    Error::new(
+        ErrorKind::InvalidData,
+        format!("invalid {}: {}", context, err),
+    )
+}
+
+/// Reads the magic bytes at the start of a variant, reporting any other bytes as `InvalidData`.
+pub fn read_magic<B: Read>(buffer: &mut B, magic: &[u8], context: &str) -> io::Result<()> {
+    let mut found = vec![0u8; magic.len()];
+    buffer.read_exact(&mut found)?;
+    check_magic(magic, &found, context)
+}
+
+#[cfg(feature = "tokio")]
+pub async fn read_magic_async<B>(buffer: &mut B, magic: &[u8], context: &str) -> io::Result<()>
+where
+    B: AsyncRead + Unpin + Send,
+{
+    let mut found = vec![0u8; magic.len()];
+    buffer.read_exact(&mut found).await?;
+    check_magic(magic, &found, context)
+}
+
+fn check_magic(magic: &[u8], found: &[u8], context: &str) -> io::Result<()> {
+    if magic == found {
+        return Ok(());
+    }
+
+    Err(Error::new(
+        ErrorKind::InvalidData,
+        format!(
+            "invalid magic for {}: expected {:02X?}, found {:02X?}",
+            context, magic, found
+        ),
+    ))
+}
+
+/// Runs one attempt at reading a variant of an untagged enum, so errors within it can be caught
+/// instead of returning from the derived function.
+#[inline(always)]
+pub fn read_attempt<T, F: FnOnce() -> io::Result<T>>(read: F) -> io::Result<T> {
+    read()
+}
+
+/// The error for an untagged enum which could not be read as any of its variants, listing the
+/// error each variant gave.
+pub fn untagged_error(name: &str, failures: Vec<(&str, Error)>) -> Error {
+    Error::new(ErrorKind::InvalidData, untagged_failures(name, &failures))
+}
+
+/// Seeks back to the start of an untagged enum before its next variant is tried. Streams which
+/// can not seek fail with the error of the seek, which also lists the variants tried so far.
+pub fn rewind_untagged<B: PositionAware + ?Sized>(
+    buffer: &mut B,
+    start: u64,
+    name: &str,
+    failures: &[(&str, Error)],
+) -> io::Result<()> {
+    buffer.seek_to(start).map_err(|err| {
+        let tried = untagged_failures(name, failures);
+        Error::new(
+            err.kind(),
+            format!(
+                "{}, and the stream could not be rewound to try the others: {}",
+                tried, err
+            ),
+        )
+    })
+}
+
+fn untagged_failures(name: &str, failures: &[(&str, Error)]) -> String {
+    let reasons = failures
+        .iter()
+        .map(|(variant, err)| format!("{}: {}", variant, err))
+        .collect::<Vec<_>>();
+
+    format!("no variant of `{}` matched ({})", name, reasons.join("; "))
+}
+
+#[cfg(feature = "tokio")]
+#[inline(always)]
+pub async fn read_string_with_length_async<B>(buffer: &mut B, len: usize) -> io::Result<String>
+where
+    B: AsyncRead + PositionAware + Unpin + Send,
+{
+    let start = buffer.position()?;
+
+    let mut bytes = Vec::new();
+    (&mut *buffer)
+        .take(len as u64)
+        .read_to_end(&mut bytes)
+        .await?;
+    if bytes.len() != len {
+        return Err(ErrorKind::UnexpectedEof.into());
+    }
+
+    utf8_at(bytes, start)
+}
+
+/// Reads an enum whose tag is stored outside of it, such as in an earlier field of the struct
+/// containing it (see `tag_from`). `O` is the byte order of the field, as with `ReadSelfEndian`.
+pub trait ReadSelfTagged<O>: Sized {
+    type Tag;
+
+    fn read_variant<B: Read + PositionAware>(tag: Self::Tag, buffer: &mut B) -> io::Result<Self>;
+}
+
+/// Writes an enum without its tag, which is instead written by the field holding it.
+pub trait WriteSelfTagged<O> {
+    type Tag;
+
+    /// The tag of the active variant.
+    fn variant_tag(&self) -> Self::Tag;
+
+    fn write_variant<B: Write + PositionAware>(&self, buffer: &mut B) -> io::Result<()>;
+}
+
+/// Writes a value with the byte order `O`. Types which do not depend on the byte order implement
+/// this for any `O` by writing through `WriteSelf`. Like `ReadSelfEndian`, types implementing
+/// `WriteSelf` by hand need `io_self::endian_agnostic!(MyType)`.
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` can not be written with a byte order",
+    note = "types implementing `WriteSelf` by hand can use `io_self::endian_agnostic!({Self})`"
+)]
 pub trait WriteSelfEndian<O>: Sized {
     fn write_to<B: Write + PositionAware>(&self, buffer: &mut B) -> io::Result<()>;
 }
 
-impl<O, T: WriteSelf> WriteSelfEndian<O> for T {
-    #[inline(always)]
-    fn write_to<B: Write + PositionAware>(&self, buffer: &mut B) -> io::Result<()> {
-        <T as WriteSelf>::write_to(self, buffer)
+#[cfg(feature = "tokio")]
+pub trait AsyncReadSelfEndian<O>: Sized + Send {
+    fn read_from_async<B>(buffer: &mut B) -> impl Future<Output = io::Result<Self>> + Send
+    where
+        B: AsyncRead + PositionAware + Unpin + Send;
+}
+
+/// The asynchronous equivalent of `read_with_length`. Readers without an endianness can use `()`
+/// for `O` since types which do not depend on the byte order implement `AsyncReadSelfEndian` for
+/// any `O`.
+#[cfg(feature = "tokio")]
+#[inline(always)]
+pub async fn read_with_length_async<O, B, T, A>(buffer: &mut B, len: usize) -> io::Result<A>
+where
+    B: AsyncRead + PositionAware + Unpin + Send,
+    T: AsyncReadSelfEndian<O>,
+    A: FromIterator<T>,
+{
+    let mut items = Vec::with_capacity(len);
+    for _ in 0..len {
+        items.push(T::read_from_async(buffer).await?);
     }
+    Ok(items.into_iter().collect())
+}
+
+#[cfg(feature = "tokio")]
+pub trait AsyncWriteSelfEndian<O>: Sized + Sync {
+    fn write_to_async<B>(&self, buffer: &mut B) -> impl Future<Output = io::Result<()>> + Send
+    where
+        B: AsyncWrite + PositionAware + Unpin + Send;
 }
 
 macro_rules! impl_for {
@@ -103,12 +349,99 @@ macro_rules! impl_for {
                 buffer.$write::<O>(*self)
             }
         }
+
+        #[cfg(feature = "tokio")]
+        impl<O: ByteOrder> AsyncReadSelfEndian<O> for $name {
+            #[inline(always)]
+            async fn read_from_async<B>(buffer: &mut B) -> io::Result<Self>
+            where
+                B: AsyncRead + PositionAware + Unpin + Send,
+            {
+                let mut bytes = [0u8; std::mem::size_of::<$name>()];
+                buffer.read_exact(&mut bytes).await?;
+                Ok(O::$read(&bytes))
+            }
+        }
+
+        #[cfg(feature = "tokio")]
+        impl<O: ByteOrder> AsyncWriteSelfEndian<O> for $name {
+            #[inline(always)]
+            async fn write_to_async<B>(&self, buffer: &mut B) -> io::Result<()>
+            where
+                B: AsyncWrite + PositionAware + Unpin + Send,
+            {
+                let mut bytes = [0u8; std::mem::size_of::<$name>()];
+                O::$write(&mut bytes, *self);
+                buffer.write_all(&bytes).await
+            }
+        }
     };
     ($($name:ty: $read:ident, $write:ident);+) => {
         $(impl_for!{$name: $read, $write})+
     }
 }
 
+/// Integers which do not fill a power of two number of bytes are read through `read_uint` and
+/// `read_int`, with the value checked before writing since `write_uint` panics if it does not fit.
+macro_rules! impl_for_width {
+    ($name:ident($inner:ty): $read:ident, $write:ident) => {
+        impl<O: ByteOrder> ReadSelfEndian<O> for $name {
+            #[inline(always)]
+            fn read_from<B: Read + PositionAware>(buffer: &mut B) -> io::Result<Self> {
+                let mut bytes = [0u8; $name::BITS as usize / 8];
+                buffer.read_exact(&mut bytes)?;
+                Ok($name(O::$read(&bytes, bytes.len()) as $inner))
+            }
+        }
+
+        impl<O: ByteOrder> WriteSelfEndian<O> for $name {
+            #[inline(always)]
+            fn write_to<B: Write + PositionAware>(&self, buffer: &mut B) -> io::Result<()> {
+                let mut bytes = [0u8; $name::BITS as usize / 8];
+                O::$write(&mut bytes, self.checked()?, $name::BITS as usize / 8);
+                buffer.write_all(&bytes)
+            }
+        }
+
+        #[cfg(feature = "tokio")]
+        impl<O: ByteOrder> AsyncReadSelfEndian<O> for $name {
+            #[inline(always)]
+            async fn read_from_async<B>(buffer: &mut B) -> io::Result<Self>
+            where
+                B: AsyncRead + PositionAware + Unpin + Send,
+            {
+                let mut bytes = [0u8; $name::BITS as usize / 8];
+                buffer.read_exact(&mut bytes).await?;
+                Ok($name(O::$read(&bytes, bytes.len()) as $inner))
+            }
+        }
+
+        #[cfg(feature = "tokio")]
+        impl<O: ByteOrder> AsyncWriteSelfEndian<O> for $name {
+            #[inline(always)]
+            async fn write_to_async<B>(&self, buffer: &mut B) -> io::Result<()>
+            where
+                B: AsyncWrite + PositionAware + Unpin + Send,
+            {
+                let mut bytes = [0u8; $name::BITS as usize / 8];
+                O::$write(&mut bytes, self.checked()?, $name::BITS as usize / 8);
+                buffer.write_all(&bytes).await
+            }
+        }
+    };
+    ($($name:ident($inner:ty): $read:ident, $write:ident);+) => {
+        $(impl_for_width!{$name($inner): $read, $write})+
+    }
+}
+
+impl_for_width! {
+    U24(u32): read_uint, write_uint;
+    I24(i32): read_int, write_int;
+    U40(u64): read_uint, write_uint;
+    U48(u64): read_uint, write_uint;
+    U56(u64): read_uint, write_uint
+}
+
 impl_for! {
     u16: read_u16, write_u16;
     u32: read_u32, write_u32;
@@ -0,0 +1,393 @@
+//! Text encodings other than UTF-8 for string fields. These are mainly used through the `encoding`
+//! attribute when deriving `ReadSelf` and `WriteSelf`.
+use crate::helper::AbortingFromIterator;
+use crate::PositionAware;
+use std::io::{self, Error, ErrorKind, Read, Write};
+
+/// The encodings a string field can be stored in.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Encoding {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    /// UTF-16 without surrogate pairs, so only characters in the basic multilingual plane can be
+    /// represented.
+    Ucs2Le,
+    Ucs2Be,
+    /// ISO-8859-1, where each byte maps directly to the code point of the same value.
+    Latin1,
+    /// The original IBM PC code page. Bytes below 0x80 are treated as ASCII.
+    Cp437,
+}
+
+/// How to handle data which can not be represented when decoding or encoding.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum ErrorPolicy {
+    /// Return an `InvalidData` error when reading or an `InvalidInput` error when writing.
+    Error,
+    /// Substitute U+FFFD when reading or `?` when writing.
+    Replace,
+    /// Preserve the raw code units by mapping them to code points in supplementary private use
+    /// area B (U+10xxxx) when reading, and back again when writing. Unpaired surrogates in UTF-16
+    /// and UCS-2 become U+10D800 to U+10DFFF, and invalid bytes in single byte encodings become
+    /// U+10FF00 to U+10FFFF. This allows invalid data to round trip, at the cost of misinterpreting
+    /// those code points when they appear in the original text for the same kind of encoding.
+    Raw,
+}
+
+/// Whether a length prefix counts bytes or code units. These are the same for single byte
+/// encodings.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum LengthUnit {
+    Bytes,
+    CodeUnits,
+}
+
+const RAW_UNIT_BASE: u32 = 0x10_0000;
+const RAW_BYTE_BASE: u32 = 0x10_FF00;
+
+const CP437_HIGH: [char; 128] = [
+    'Ç', 'ü', 'é', 'â', 'ä', 'à', 'å', 'ç', 'ê', 'ë', 'è', 'ï', 'î', 'ì', 'Ä', 'Å', //
+    'É', 'æ', 'Æ', 'ô', 'ö', 'ò', 'û', 'ù', 'ÿ', 'Ö', 'Ü', '¢', '£', '¥', '₧', 'ƒ', //
+    'á', 'í', 'ó', 'ú', 'ñ', 'Ñ', 'ª', 'º', '¿', '⌐', '¬', '½', '¼', '¡', '«', '»', //
+    '░', '▒', '▓', '│', '┤', '╡', '╢', '╖', '╕', '╣', '║', '╗', '╝', '╜', '╛', '┐', //
+    '└', '┴', '┬', '├', '─', '┼', '╞', '╟', '╚', '╔', '╩', '╦', '╠', '═', '╬', '╧', //
+    '╨', '╤', '╥', '╙', '╘', '╒', '╓', '╫', '╪', '┘', '┌', '█', '▄', '▌', '▐', '▀', //
+    'α', 'ß', 'Γ', 'π', 'Σ', 'σ', 'µ', 'τ', 'Φ', 'Θ', 'Ω', 'δ', '∞', 'φ', 'ε', '∩', //
+    '≡', '±', '≥', '≤', '⌠', '⌡', '÷', '≈', '°', '∙', '·', '√', 'ⁿ', '²', '■', '\u{A0}', //
+];
+
+impl Encoding {
+    /// The size of a single code unit in bytes.
+    pub fn unit_size(self) -> usize {
+        match self {
+            Encoding::Utf16Le | Encoding::Utf16Be | Encoding::Ucs2Le | Encoding::Ucs2Be => 2,
+            _ => 1,
+        }
+    }
+
+    fn unit_at(self, bytes: &[u8], idx: usize) -> u16 {
+        match self {
+            Encoding::Utf16Le | Encoding::Ucs2Le => {
+                u16::from_le_bytes([bytes[idx], bytes[idx + 1]])
+            }
+            Encoding::Utf16Be | Encoding::Ucs2Be => {
+                u16::from_be_bytes([bytes[idx], bytes[idx + 1]])
+            }
+            _ => bytes[idx] as u16,
+        }
+    }
+
+    fn push_unit(self, bytes: &mut Vec<u8>, unit: u16) {
+        match self {
+            Encoding::Utf16Le | Encoding::Ucs2Le => bytes.extend_from_slice(&unit.to_le_bytes()),
+            Encoding::Utf16Be | Encoding::Ucs2Be => bytes.extend_from_slice(&unit.to_be_bytes()),
+            _ => bytes.push(unit as u8),
+        }
+    }
+
+    /// Decode `bytes` which were read starting at `position`. The position is only used to report
+    /// where invalid data was found.
+    pub fn decode(self, bytes: &[u8], policy: ErrorPolicy, position: u64) -> io::Result<String> {
+        let unit_size = self.unit_size();
+        if !bytes.len().is_multiple_of(unit_size) {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("{} bytes is not a whole number of code units", bytes.len()),
+            ));
+        }
+
+        let invalid = |idx: usize, raw: u32| -> io::Result<char> {
+            match policy {
+                ErrorPolicy::Error => Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("invalid {:?} at offset {}", self, position + idx as u64),
+                )),
+                ErrorPolicy::Replace => Ok(char::REPLACEMENT_CHARACTER),
+                ErrorPolicy::Raw => Ok(char::from_u32(raw).unwrap()),
+            }
+        };
+
+        match self {
+            Encoding::Utf8 => {
+                let mut text = String::with_capacity(bytes.len());
+                let mut idx = 0;
+
+                for chunk in bytes.utf8_chunks() {
+                    text.push_str(chunk.valid());
+                    idx += chunk.valid().len();
+
+                    for byte in chunk.invalid() {
+                        text.push(invalid(idx, RAW_BYTE_BASE + *byte as u32)?);
+                        idx += 1;
+                    }
+                }
+                Ok(text)
+            }
+            Encoding::Latin1 => Ok(bytes.iter().map(|x| *x as char).collect()),
+            Encoding::Cp437 => Ok(bytes
+                .iter()
+                .map(|x| match *x {
+                    x @ 0..=0x7F => x as char,
+                    x => CP437_HIGH[x as usize - 0x80],
+                })
+                .collect()),
+            Encoding::Ucs2Le | Encoding::Ucs2Be => (0..bytes.len())
+                .step_by(2)
+                .map(|idx| {
+                    let unit = self.unit_at(bytes, idx);
+                    match char::from_u32(unit as u32) {
+                        Some(c) => Ok(c),
+                        None => invalid(idx, RAW_UNIT_BASE + unit as u32),
+                    }
+                })
+                .aborting_from_iter(),
+            Encoding::Utf16Le | Encoding::Utf16Be => {
+                let units = (0..bytes.len())
+                    .step_by(2)
+                    .map(|idx| self.unit_at(bytes, idx));
+                let mut text = String::with_capacity(bytes.len() / 2);
+                let mut idx = 0;
+
+                for c in char::decode_utf16(units) {
+                    match c {
+                        Ok(c) => {
+                            text.push(c);
+                            idx += c.len_utf16() * 2;
+                        }
+                        Err(e) => {
+                            let unit = e.unpaired_surrogate();
+                            text.push(invalid(idx, RAW_UNIT_BASE + unit as u32)?);
+                            idx += 2;
+                        }
+                    }
+                }
+                Ok(text)
+            }
+        }
+    }
+
+    /// Encode `text`, returning the bytes along with the number of code units.
+    pub fn encode(self, text: &str, policy: ErrorPolicy) -> io::Result<(Vec<u8>, usize)> {
+        let mut bytes = Vec::with_capacity(text.len() * self.unit_size());
+
+        for c in text.chars() {
+            let raw = c as u32;
+            // Only code points which decoding could have produced for this encoding are raw units,
+            // so the same code points are still encoded normally by other encodings
+            let raw_unit = match self.unit_size() {
+                1 if raw >= RAW_BYTE_BASE => Some(raw - RAW_BYTE_BASE),
+                2 if (RAW_UNIT_BASE + 0xD800..RAW_UNIT_BASE + 0xE000).contains(&raw) => {
+                    Some(raw - RAW_UNIT_BASE)
+                }
+                _ => None,
+            };
+
+            if let (ErrorPolicy::Raw, Some(unit)) = (policy, raw_unit) {
+                match self.unit_size() {
+                    1 => bytes.push(unit as u8),
+                    _ => self.push_unit(&mut bytes, unit as u16),
+                }
+                continue;
+            }
+
+            match self {
+                Encoding::Utf8 => bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes()),
+                Encoding::Latin1 if raw <= 0xFF => bytes.push(raw as u8),
+                Encoding::Cp437 if raw < 0x80 => bytes.push(raw as u8),
+                Encoding::Cp437 => match CP437_HIGH.iter().position(|x| *x == c) {
+                    Some(idx) => bytes.push(0x80 + idx as u8),
+                    None => self.unmappable(&mut bytes, c, policy)?,
+                },
+                Encoding::Ucs2Le | Encoding::Ucs2Be if raw <= 0xFFFF => {
+                    self.push_unit(&mut bytes, raw as u16)
+                }
+                Encoding::Utf16Le | Encoding::Utf16Be => {
+                    for unit in c.encode_utf16(&mut [0; 2]) {
+                        self.push_unit(&mut bytes, *unit);
+                    }
+                }
+                _ => self.unmappable(&mut bytes, c, policy)?,
+            }
+        }
+
+        let units = bytes.len() / self.unit_size();
+        Ok((bytes, units))
+    }
+
+    fn unmappable(self, bytes: &mut Vec<u8>, c: char, policy: ErrorPolicy) -> io::Result<()> {
+        match policy {
+            ErrorPolicy::Replace => {
+                self.push_unit(bytes, b'?' as u16);
+                Ok(())
+            }
+            _ => Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("{:?} can not be encoded as {:?}", c, self),
+            )),
+        }
+    }
+}
+
+/// An encoding along with the policy for handling errors. This provides the layouts which can be
+/// used for string fields.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct TextFormat {
+    pub encoding: Encoding,
+    pub policy: ErrorPolicy,
+}
+
+impl TextFormat {
+    pub fn new(encoding: Encoding, policy: ErrorPolicy) -> Self {
+        TextFormat { encoding, policy }
+    }
+
+    fn length_in_bytes(&self, len: usize, unit: LengthUnit) -> io::Result<usize> {
+        match unit {
+            LengthUnit::Bytes => Ok(len),
+            LengthUnit::CodeUnits => len
+                .checked_mul(self.encoding.unit_size())
+                .ok_or_else(|| Error::new(ErrorKind::InvalidData, "string length overflowed")),
+        }
+    }
+
+    /// Read a string after its length has been read.
+    pub fn read_with_length<B>(
+        &self,
+        buffer: &mut B,
+        len: usize,
+        unit: LengthUnit,
+    ) -> io::Result<String>
+    where
+        B: Read + PositionAware,
+    {
+        let len = self.length_in_bytes(len, unit)?;
+        let start = buffer.position()?;
+
+        // Avoid trusting the length with a large allocation before the bytes have actually been read
+        let mut bytes = Vec::new();
+        buffer.take(len as u64).read_to_end(&mut bytes)?;
+        if bytes.len() != len {
+            return Err(ErrorKind::UnexpectedEof.into());
+        }
+
+        self.encoding.decode(&bytes, self.policy, start)
+    }
+
+    /// Read a string terminated by a null code unit. Reading fails if the terminator is not found
+    /// within `max_len` bytes.
+    pub fn read_terminated<B>(&self, buffer: &mut B, max_len: usize) -> io::Result<String>
+    where
+        B: Read + PositionAware,
+    {
+        let start = buffer.position()?;
+        let unit_size = self.encoding.unit_size();
+        let mut bytes = Vec::new();
+        let mut unit = [0u8; 2];
+
+        loop {
+            buffer.read_exact(&mut unit[..unit_size])?;
+            if unit[..unit_size].iter().all(|x| *x == 0) {
+                return self.encoding.decode(&bytes, self.policy, start);
+            }
+
+            if bytes.len() >= max_len {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("terminator not found within {} bytes", max_len),
+                ));
+            }
+            bytes.extend_from_slice(&unit[..unit_size]);
+        }
+    }
+
+    /// Read a string stored in a field of `size` bytes padded with null code units. Decoding stops
+    /// at the first null code unit.
+    pub fn read_fixed<B>(&self, buffer: &mut B, size: usize) -> io::Result<String>
+    where
+        B: Read + PositionAware,
+    {
+        let start = buffer.position()?;
+        let mut bytes = vec![0u8; size];
+        buffer.read_exact(&mut bytes)?;
+
+        let unit_size = self.encoding.unit_size();
+        let end = bytes
+            .chunks(unit_size)
+            .position(|unit| unit.iter().all(|x| *x == 0))
+            .map_or(size, |idx| idx * unit_size);
+
+        self.encoding.decode(&bytes[..end], self.policy, start)
+    }
+
+    /// Write a string preceded by its length.
+    pub fn write_with_prefix<P, B, G>(
+        &self,
+        value: &str,
+        buffer: &mut B,
+        unit: LengthUnit,
+        prefix_writer: G,
+    ) -> io::Result<()>
+    where
+        P: TryFrom<usize>,
+        <P as TryFrom<usize>>::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+        B: Write + PositionAware,
+        G: FnOnce(&P, &mut B) -> io::Result<()>,
+    {
+        let (bytes, units) = self.encoding.encode(value, self.policy)?;
+        let len = match unit {
+            LengthUnit::Bytes => bytes.len(),
+            LengthUnit::CodeUnits => units,
+        };
+
+        let length_prefix = P::try_from(len).map_err(Error::other)?;
+        prefix_writer(&length_prefix, buffer)?;
+        buffer.write_all(&bytes)
+    }
+
+    /// Write a string followed by a null code unit.
+    pub fn write_terminated<B: Write + PositionAware>(
+        &self,
+        value: &str,
+        buffer: &mut B,
+    ) -> io::Result<()> {
+        let (mut bytes, _) = self.encoding.encode(value, self.policy)?;
+        let unit_size = self.encoding.unit_size();
+
+        if bytes
+            .chunks(unit_size)
+            .any(|unit| unit.iter().all(|x| *x == 0))
+        {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "value contains its own terminator",
+            ));
+        }
+
+        bytes.extend_from_slice(&[0, 0][..unit_size]);
+        buffer.write_all(&bytes)
+    }
+
+    /// Write a string into a field of `size` bytes padded with null bytes.
+    pub fn write_fixed<B: Write + PositionAware>(
+        &self,
+        value: &str,
+        buffer: &mut B,
+        size: usize,
+    ) -> io::Result<()> {
+        let (bytes, _) = self.encoding.encode(value, self.policy)?;
+        if bytes.len() > size {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "{} bytes does not fit in a field of {} bytes",
+                    bytes.len(),
+                    size
+                ),
+            ));
+        }
+
+        buffer.write_all(&bytes)?;
+        crate::grammar::write_padding(buffer, 0, size - bytes.len())
+    }
+}
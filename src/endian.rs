@@ -0,0 +1,525 @@
+//! Wrappers which fix the byte order of a value so it can be read or written through `ReadSelf`
+//! and `WriteSelf` directly, without deriving an implementation. This allows values such as
+//! `Vec<Be<u32>>` or `(Le<u16>, Le<i64>)` to be used anywhere a `ReadSelf` type is accepted.
+//!
+//! Types deriving with `#[io_self(endian = "generic")]` implement `ReadSelfEndian<O>` and
+//! `WriteSelfEndian<O>` for any `O: ByteOrder` instead, so the byte order is chosen by the parent
+//! (Ex: `Be<Header>` or a field of a struct with a fixed endianness).
+//!
+//! Fields of a struct with an endianness are read and written through `ReadSelfEndian` and
+//! `WriteSelfEndian`. These are no longer implemented for every `ReadSelf` and `WriteSelf` type,
+//! since that blanket impl prevented containers such as `Vec<T>` or `[T; N]` from passing the byte
+//! order on to their items. Derived types still implement them for any byte order, but types which
+//! implement `ReadSelf` and `WriteSelf` by hand need `endian_agnostic!` to be used as such a field:
+//!
+//! ```ignore
+//! impl ReadSelf for Manual { /* ... */ }
+//! impl WriteSelf for Manual { /* ... */ }
+//!
+//! io_self::endian_agnostic!(Manual);
+//! ```
+//!
+//! `Vec<T>` and `Option<T>` only implement `WriteSelfEndian`. Their length is not part of their
+//! encoding, so there is nothing to read them from without a count or an end of stream to stop
+//! at. Derived fields of these types give that context with `length_prefix` or `until_eof`, and
+//! their items are still read with the byte order of the struct.
+#[cfg(feature = "tokio")]
+pub use crate::derive_util::{AsyncReadSelfEndian, AsyncWriteSelfEndian};
+pub use crate::derive_util::{ReadSelfEndian, WriteSelfEndian};
+pub use byteorder::{BigEndian, ByteOrder, LittleEndian};
+
+use crate::grammar::{
+    ByteTerminatedVec, FixedStr, PadToAlign, Padding, TerminatedString, VarI64, VarU32, VarU64,
+};
+use crate::TagValue;
+use crate::{PositionAware, ReadSelf, WriteSelf};
+use std::ffi::{CStr, CString};
+use std::io::{self, Read, Write};
+use std::marker::PhantomData;
+use std::mem::MaybeUninit;
+use std::ops::{Deref, DerefMut};
+use std::rc::Rc;
+use std::sync::Arc;
+
+#[cfg(feature = "tokio")]
+use crate::async_io::{AsyncReadSelf, AsyncWriteSelf};
+#[cfg(feature = "tokio")]
+use tokio::io::{AsyncRead, AsyncWrite};
+
+macro_rules! impl_endian_wrapper {
+    ($(#[$meta:meta])* $name:ident: $order:ty) => {
+        $(#[$meta])*
+        #[derive(Default, Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+        pub struct $name<T>(pub T);
+
+        impl<T> $name<T> {
+            pub fn into_inner(self) -> T {
+                self.0
+            }
+        }
+
+        impl<T> From<T> for $name<T> {
+            fn from(value: T) -> Self {
+                $name(value)
+            }
+        }
+
+        impl<T> Deref for $name<T> {
+            type Target = T;
+
+            fn deref(&self) -> &Self::Target {
+                &self.0
+            }
+        }
+
+        impl<T> DerefMut for $name<T> {
+            fn deref_mut(&mut self) -> &mut Self::Target {
+                &mut self.0
+            }
+        }
+
+        impl<T: TagValue> TagValue for $name<T> {
+            type Value = T::Value;
+
+            fn into_value(self) -> Self::Value {
+                self.0.into_value()
+            }
+
+            fn from_value(value: Self::Value) -> Self {
+                $name(T::from_value(value))
+            }
+        }
+
+        impl<T: ReadSelfEndian<$order>> ReadSelf for $name<T> {
+            #[inline(always)]
+            fn read_from<B: Read + PositionAware>(buffer: &mut B) -> io::Result<Self> {
+                T::read_from(buffer).map($name)
+            }
+        }
+
+        impl<T: WriteSelfEndian<$order>> WriteSelf for $name<T> {
+            #[inline(always)]
+            fn write_to<B: Write + PositionAware>(&self, buffer: &mut B) -> io::Result<()> {
+                self.0.write_to(buffer)
+            }
+        }
+
+        #[cfg(feature = "tokio")]
+        impl<T: AsyncReadSelfEndian<$order>> AsyncReadSelf for $name<T> {
+            #[inline(always)]
+            async fn read_from_async<B>(buffer: &mut B) -> io::Result<Self>
+            where
+                B: AsyncRead + PositionAware + Unpin + Send,
+            {
+                T::read_from_async(buffer).await.map($name)
+            }
+        }
+
+        #[cfg(feature = "tokio")]
+        impl<T: AsyncWriteSelfEndian<$order>> AsyncWriteSelf for $name<T> {
+            #[inline(always)]
+            async fn write_to_async<B>(&self, buffer: &mut B) -> io::Result<()>
+            where
+                B: AsyncWrite + PositionAware + Unpin + Send,
+            {
+                self.0.write_to_async(buffer).await
+            }
+        }
+    };
+}
+
+impl_endian_wrapper! {
+    /// A value stored in big endian byte order.
+    Be: BigEndian
+}
+
+impl_endian_wrapper! {
+    /// A value stored in little endian byte order.
+    Le: LittleEndian
+}
+
+/// Implements `ReadSelfEndian<O>` and `WriteSelfEndian<O>` for any `O` by reading and writing
+/// through `ReadSelf` and `WriteSelf`. Types which derive `ReadSelf` and `WriteSelf` get these
+/// impls automatically, but types implementing them by hand need this to be used as a field of a
+/// struct with an endianness. Generic parameters are given in brackets before the type. Array
+/// types always need the brackets, even when empty (Ex: `[] [u8; 4]`), since their own brackets
+/// would otherwise be read as the generic parameters.
+///
+/// ```
+/// use io_self::endian::{BigEndian, ReadSelfEndian};
+/// use io_self::{PositionAware, ReadSelf, WriteSelf};
+/// use std::io::{self, Cursor, Read, Write};
+///
+/// pub struct Header(u8);
+///
+/// impl ReadSelf for Header {
+///     fn read_from<B: Read + PositionAware>(buffer: &mut B) -> io::Result<Self> {
+///         let mut byte = [0];
+///         buffer.read_exact(&mut byte)?;
+///         Ok(Header(byte[0]))
+///     }
+/// }
+///
+/// impl WriteSelf for Header {
+///     fn write_to<B: Write + PositionAware>(&self, buffer: &mut B) -> io::Result<()> {
+///         buffer.write_all(&[self.0])
+///     }
+/// }
+///
+/// pub struct Wrapper<T>(T);
+///
+/// impl<T: ReadSelf> ReadSelf for Wrapper<T> {
+///     fn read_from<B: Read + PositionAware>(buffer: &mut B) -> io::Result<Self> {
+///         T::read_from(buffer).map(Wrapper)
+///     }
+/// }
+///
+/// io_self::endian_agnostic!(Header, [T] Wrapper<T>);
+///
+/// let mut cursor = Cursor::new([7]);
+/// let header = <Wrapper<Header> as ReadSelfEndian<BigEndian>>::read_from(&mut cursor).unwrap();
+/// assert_eq!((header.0).0, 7);
+/// ```
+#[macro_export]
+macro_rules! endian_agnostic {
+    () => {};
+    ([$($generics:tt)*] $ty:ty $(, $($rest:tt)*)?) => {
+        impl<__O, $($generics)*> $crate::endian::ReadSelfEndian<__O> for $ty
+        where
+            $ty: $crate::ReadSelf,
+        {
+            #[inline(always)]
+            fn read_from<__B>(buffer: &mut __B) -> ::std::io::Result<Self>
+            where
+                __B: ::std::io::Read + $crate::PositionAware,
+            {
+                <$ty as $crate::ReadSelf>::read_from(buffer)
+            }
+        }
+
+        impl<__O, $($generics)*> $crate::endian::WriteSelfEndian<__O> for $ty
+        where
+            $ty: $crate::WriteSelf,
+        {
+            #[inline(always)]
+            fn write_to<__B>(&self, buffer: &mut __B) -> ::std::io::Result<()>
+            where
+                __B: ::std::io::Write + $crate::PositionAware,
+            {
+                <$ty as $crate::WriteSelf>::write_to(self, buffer)
+            }
+        }
+
+        $crate::endian_agnostic!($($($rest)*)?);
+    };
+    ($ty:ty $(, $($rest:tt)*)?) => {
+        $crate::endian_agnostic!([] $ty $(, $($rest)*)?);
+    };
+}
+
+/// The asynchronous counterpart of `endian_agnostic!` for types within this crate.
+#[cfg(feature = "tokio")]
+macro_rules! async_endian_agnostic {
+    ($([$($generics:tt)*] $ty:ty),+ $(,)?) => {
+        $(
+            impl<__O, $($generics)*> AsyncReadSelfEndian<__O> for $ty
+            where
+                $ty: AsyncReadSelf,
+            {
+                #[inline(always)]
+                async fn read_from_async<B>(buffer: &mut B) -> io::Result<Self>
+                where
+                    B: AsyncRead + PositionAware + Unpin + Send,
+                {
+                    <$ty as AsyncReadSelf>::read_from_async(buffer).await
+                }
+            }
+
+            impl<__O, $($generics)*> AsyncWriteSelfEndian<__O> for $ty
+            where
+                $ty: AsyncWriteSelf,
+            {
+                #[inline(always)]
+                async fn write_to_async<B>(&self, buffer: &mut B) -> io::Result<()>
+                where
+                    B: AsyncWrite + PositionAware + Unpin + Send,
+                {
+                    <$ty as AsyncWriteSelf>::write_to_async(self, buffer).await
+                }
+            }
+        )+
+    };
+}
+
+endian_agnostic! {
+    [] u8,
+    [] i8,
+    [] (),
+    [T: ?Sized] PhantomData<T>,
+    [] CString,
+    [const N: usize] Padding<N>,
+    [const N: u64, const P: u8] PadToAlign<N, P>,
+    [T, const N: u8, const M: usize] ByteTerminatedVec<T, N, M>,
+    [const N: u8, const M: usize] TerminatedString<N, M>,
+    [const N: usize, R] FixedStr<N, R>,
+    [F] VarU32<F>,
+    [F] VarU64<F>,
+    [F] VarI64<F>,
+    [T] Be<T>,
+    [T] Le<T>,
+}
+
+#[cfg(feature = "tokio")]
+async_endian_agnostic! {
+    [] u8,
+    [] i8,
+    [] (),
+    [T: ?Sized] PhantomData<T>,
+    [const N: usize] Padding<N>,
+    [const N: u64, const P: u8] PadToAlign<N, P>,
+    [F] VarU32<F>,
+    [F] VarU64<F>,
+    [F] VarI64<F>,
+    [T] Be<T>,
+    [T] Le<T>,
+}
+
+impl<O> WriteSelfEndian<O> for &CStr {
+    #[inline(always)]
+    fn write_to<B: Write + PositionAware>(&self, buffer: &mut B) -> io::Result<()> {
+        <&CStr as WriteSelf>::write_to(self, buffer)
+    }
+}
+
+macro_rules! impl_pointer_endian {
+    ($($pointer:ident),+) => {
+        $(
+            impl<O, T: ReadSelfEndian<O>> ReadSelfEndian<O> for $pointer<T> {
+                #[inline(always)]
+                fn read_from<B: Read + PositionAware>(buffer: &mut B) -> io::Result<Self> {
+                    T::read_from(buffer).map($pointer::new)
+                }
+            }
+
+            impl<O, T: WriteSelfEndian<O>> WriteSelfEndian<O> for $pointer<T> {
+                #[inline(always)]
+                fn write_to<B: Write + PositionAware>(&self, buffer: &mut B) -> io::Result<()> {
+                    T::write_to(self, buffer)
+                }
+            }
+        )+
+    };
+}
+
+impl_pointer_endian!(Box, Rc, Arc);
+
+impl<O, T: ReadSelfEndian<O>, const N: usize> ReadSelfEndian<O> for [T; N] {
+    #[inline(always)]
+    fn read_from<B: Read + PositionAware>(buffer: &mut B) -> io::Result<Self> {
+        unsafe {
+            let mut array = MaybeUninit::<[MaybeUninit<T>; N]>::uninit().assume_init();
+
+            for item in array.iter_mut().take(N) {
+                item.write(T::read_from(buffer)?);
+            }
+
+            Ok((&array as *const _ as *const [T; N]).read())
+        }
+    }
+}
+
+impl<O, T: WriteSelfEndian<O>, const N: usize> WriteSelfEndian<O> for [T; N] {
+    #[inline(always)]
+    fn write_to<B: Write + PositionAware>(&self, buffer: &mut B) -> io::Result<()> {
+        for item in self {
+            item.write_to(buffer)?;
+        }
+        Ok(())
+    }
+}
+
+/// There is no matching `ReadSelfEndian` impl, since the number of items is not written.
+impl<O, T: WriteSelfEndian<O>> WriteSelfEndian<O> for Vec<T> {
+    #[inline(always)]
+    fn write_to<B: Write + PositionAware>(&self, buffer: &mut B) -> io::Result<()> {
+        for item in self {
+            item.write_to(buffer)?;
+        }
+        Ok(())
+    }
+}
+
+/// Nothing is written for `None`, so there is no matching `ReadSelfEndian` impl.
+impl<O, T: WriteSelfEndian<O>> WriteSelfEndian<O> for Option<T> {
+    #[inline(always)]
+    fn write_to<B: Write + PositionAware>(&self, buffer: &mut B) -> io::Result<()> {
+        match self {
+            Some(value) => value.write_to(buffer),
+            None => Ok(()),
+        }
+    }
+}
+
+macro_rules! impl_tuple_endian {
+    ($($generic:ident)*) => {
+        impl<O, $($generic: ReadSelfEndian<O>),*> ReadSelfEndian<O> for ($($generic),*) {
+            #[inline(always)]
+            fn read_from<Buf: Read + PositionAware>(buffer: &mut Buf) -> io::Result<Self> {
+                Ok(($(<$generic as ReadSelfEndian<O>>::read_from(buffer)?),*))
+            }
+        }
+
+        impl<O, $($generic: WriteSelfEndian<O>),*> WriteSelfEndian<O> for ($($generic),*) {
+            #[inline(always)]
+            fn write_to<Buf: Write + PositionAware>(&self, buffer: &mut Buf) -> io::Result<()> {
+                #[allow(non_snake_case)]
+                let ($($generic),*) = self;
+                $(<$generic as WriteSelfEndian<O>>::write_to($generic, buffer)?;)*
+                Ok(())
+            }
+        }
+
+        #[cfg(feature = "tokio")]
+        impl<O, $($generic: AsyncReadSelfEndian<O>),*> AsyncReadSelfEndian<O> for ($($generic),*) {
+            #[inline(always)]
+            async fn read_from_async<Buf>(buffer: &mut Buf) -> io::Result<Self>
+            where
+                Buf: AsyncRead + PositionAware + Unpin + Send,
+            {
+                Ok(($(<$generic as AsyncReadSelfEndian<O>>::read_from_async(buffer).await?),*))
+            }
+        }
+
+        #[cfg(feature = "tokio")]
+        impl<O, $($generic: AsyncWriteSelfEndian<O>),*> AsyncWriteSelfEndian<O> for ($($generic),*) {
+            #[inline(always)]
+            async fn write_to_async<Buf>(&self, buffer: &mut Buf) -> io::Result<()>
+            where
+                Buf: AsyncWrite + PositionAware + Unpin + Send,
+            {
+                #[allow(non_snake_case)]
+                let ($($generic),*) = self;
+                $(<$generic as AsyncWriteSelfEndian<O>>::write_to_async($generic, buffer).await?;)*
+                Ok(())
+            }
+        }
+    };
+}
+
+impl_tuple_endian! {A B}
+impl_tuple_endian! {A B C}
+impl_tuple_endian! {A B C D}
+impl_tuple_endian! {A B C D E}
+impl_tuple_endian! {A B C D E F}
+impl_tuple_endian! {A B C D E F G}
+impl_tuple_endian! {A B C D E F G H}
+impl_tuple_endian! {A B C D E F G H I}
+impl_tuple_endian! {A B C D E F G H I J}
+impl_tuple_endian! {A B C D E F G H I J K}
+impl_tuple_endian! {A B C D E F G H I J K L}
+impl_tuple_endian! {A B C D E F G H I J K L M}
+impl_tuple_endian! {A B C D E F G H I J K L M N}
+
+#[cfg(feature = "tokio")]
+impl<O, T: AsyncReadSelfEndian<O>> AsyncReadSelfEndian<O> for Box<T> {
+    #[inline(always)]
+    async fn read_from_async<B>(buffer: &mut B) -> io::Result<Self>
+    where
+        B: AsyncRead + PositionAware + Unpin + Send,
+    {
+        Ok(Box::new(T::read_from_async(buffer).await?))
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<O, T: AsyncWriteSelfEndian<O> + Send> AsyncWriteSelfEndian<O> for Box<T> {
+    #[inline(always)]
+    async fn write_to_async<B>(&self, buffer: &mut B) -> io::Result<()>
+    where
+        B: AsyncWrite + PositionAware + Unpin + Send,
+    {
+        T::write_to_async(self, buffer).await
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<O, T: AsyncReadSelfEndian<O> + Sync> AsyncReadSelfEndian<O> for Arc<T> {
+    #[inline(always)]
+    async fn read_from_async<B>(buffer: &mut B) -> io::Result<Self>
+    where
+        B: AsyncRead + PositionAware + Unpin + Send,
+    {
+        Ok(Arc::new(T::read_from_async(buffer).await?))
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<O, T: AsyncWriteSelfEndian<O> + Send> AsyncWriteSelfEndian<O> for Arc<T> {
+    #[inline(always)]
+    async fn write_to_async<B>(&self, buffer: &mut B) -> io::Result<()>
+    where
+        B: AsyncWrite + PositionAware + Unpin + Send,
+    {
+        T::write_to_async(self, buffer).await
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<O, T: AsyncReadSelfEndian<O>, const N: usize> AsyncReadSelfEndian<O> for [T; N] {
+    #[inline(always)]
+    async fn read_from_async<B>(buffer: &mut B) -> io::Result<Self>
+    where
+        B: AsyncRead + PositionAware + Unpin + Send,
+    {
+        let mut array = unsafe { MaybeUninit::<[MaybeUninit<T>; N]>::uninit().assume_init() };
+
+        for item in array.iter_mut().take(N) {
+            item.write(T::read_from_async(buffer).await?);
+        }
+
+        unsafe { Ok((&array as *const _ as *const [T; N]).read()) }
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<O, T: AsyncWriteSelfEndian<O>, const N: usize> AsyncWriteSelfEndian<O> for [T; N] {
+    #[inline(always)]
+    async fn write_to_async<B>(&self, buffer: &mut B) -> io::Result<()>
+    where
+        B: AsyncWrite + PositionAware + Unpin + Send,
+    {
+        for item in self {
+            item.write_to_async(buffer).await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<O, T: AsyncWriteSelfEndian<O> + Send> AsyncWriteSelfEndian<O> for Vec<T> {
+    #[inline(always)]
+    async fn write_to_async<B>(&self, buffer: &mut B) -> io::Result<()>
+    where
+        B: AsyncWrite + PositionAware + Unpin + Send,
+    {
+        for item in self {
+            item.write_to_async(buffer).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Nothing is written for `None`.
+#[cfg(feature = "tokio")]
+impl<O, T: AsyncWriteSelfEndian<O> + Send> AsyncWriteSelfEndian<O> for Option<T> {
+    #[inline(always)]
+    async fn write_to_async<B>(&self, buffer: &mut B) -> io::Result<()>
+    where
+        B: AsyncWrite + PositionAware + Unpin + Send,
+    {
+        match self {
+            Some(value) => value.write_to_async(buffer).await,
+            None => Ok(()),
+        }
+    }
+}
@@ -1,9 +1,16 @@
+use crate::helper::{read_terminated, read_terminated_buffered, read_until_eof};
 use crate::{PositionAware, ReadIntoSelf, ReadSelf, WriteSelf};
 use std::ffi::{CStr, CString};
 use std::io;
-use std::io::{Read, Write};
+use std::io::{BufRead, Cursor, Error, ErrorKind, Read, Write};
+use std::marker::PhantomData;
 use std::ops::{Deref, DerefMut};
 
+#[cfg(feature = "tokio")]
+use crate::async_io::{AsyncReadSelf, AsyncWriteSelf};
+#[cfg(feature = "tokio")]
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
 const IO_CHUNK_SIZE: usize = 512;
 
 pub fn consume_bytes<B: Read>(buffer: &mut B, mut len: usize) -> std::io::Result<()> {
@@ -30,6 +37,39 @@ pub fn write_padding<B: Write>(buffer: &mut B, byte: u8, mut len: usize) -> std:
     Ok(())
 }
 
+#[cfg(feature = "tokio")]
+pub async fn consume_bytes_async<B: AsyncRead + Unpin>(
+    buffer: &mut B,
+    mut len: usize,
+) -> std::io::Result<()> {
+    let mut _dumped = [0u8; IO_CHUNK_SIZE];
+
+    while len > 0 {
+        let read_size = len.min(IO_CHUNK_SIZE);
+        buffer.read_exact(&mut _dumped[..read_size]).await?;
+        len -= read_size;
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "tokio")]
+pub async fn write_padding_async<B: AsyncWrite + Unpin>(
+    buffer: &mut B,
+    byte: u8,
+    mut len: usize,
+) -> std::io::Result<()> {
+    let padding = [byte; IO_CHUNK_SIZE];
+
+    while len > 0 {
+        let write_size = len.min(IO_CHUNK_SIZE);
+        buffer.write_all(&padding[..write_size]).await?;
+        len -= write_size;
+    }
+
+    Ok(())
+}
+
 /// A zero-sized type which consumes a specified number of bytes when reading without allocating any
 /// memory.
 #[derive(Default, Debug, Copy, Clone)]
@@ -54,6 +94,27 @@ impl<const N: usize> WriteSelf for Padding<N> {
     }
 }
 
+#[cfg(feature = "tokio")]
+impl<const N: usize> AsyncReadSelf for Padding<N> {
+    async fn read_from_async<B>(buffer: &mut B) -> io::Result<Self>
+    where
+        B: AsyncRead + PositionAware + Unpin + Send,
+    {
+        consume_bytes_async(buffer, N).await?;
+        Ok(Padding)
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<const N: usize> AsyncWriteSelf for Padding<N> {
+    async fn write_to_async<B>(&self, buffer: &mut B) -> io::Result<()>
+    where
+        B: AsyncWrite + PositionAware + Unpin + Send,
+    {
+        write_padding_async(buffer, 0, N).await
+    }
+}
+
 /// Some file formats require that values be stored at a specific alignment so it can be used
 /// directly after being read into memory. From the cases I have seen, these requirements become
 /// outdated as newer systems make the performance gains negligible. However it is not unusual to
@@ -105,17 +166,59 @@ impl<const N: u64, const P: u8> WriteSelf for PadToAlign<N, P> {
     }
 }
 
-pub struct ByteTerminatedVec<T, const TERMINATOR: u8 = b'\0'> {
+#[cfg(feature = "tokio")]
+impl<const N: u64, const P: u8> AsyncReadSelf for PadToAlign<N, P> {
+    async fn read_from_async<B>(buffer: &mut B) -> io::Result<Self>
+    where
+        B: AsyncRead + PositionAware + Unpin + Send,
+    {
+        let padding = Self::padding_for(buffer.position()?) as usize;
+        consume_bytes_async(buffer, padding).await?;
+        Ok(PadToAlign)
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<const N: u64, const P: u8> AsyncWriteSelf for PadToAlign<N, P> {
+    async fn write_to_async<B>(&self, buffer: &mut B) -> io::Result<()>
+    where
+        B: AsyncWrite + PositionAware + Unpin + Send,
+    {
+        let padding = Self::padding_for(buffer.position()?) as usize;
+        write_padding_async(buffer, P, padding).await
+    }
+}
+
+/// A list of items followed by a terminating byte. The terminator is searched for in the raw bytes
+/// of the stream, so it must not appear within the encoding of any item. If the terminator is not
+/// found within `MAX_LEN` bytes (`CSTRING_MAX_LEN` by default), reading fails with an
+/// `InvalidData` error instead of consuming the rest of the stream.
+///
+/// `ReadSelf` only requires `Read`, so `read_from` reads one byte at a time to avoid consuming
+/// anything past the terminator. This is also what derived structs use. When the stream is a
+/// `BufRead`, call `read_buffered` instead to search its buffer directly.
+#[derive(Default, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ByteTerminatedVec<
+    T,
+    const TERMINATOR: u8 = b'\0',
+    const MAX_LEN: usize = CSTRING_MAX_LEN,
+> {
     inner: Vec<T>,
 }
 
-impl<T, const N: u8> From<ByteTerminatedVec<T, N>> for Vec<T> {
-    fn from(x: ByteTerminatedVec<T, N>) -> Self {
+impl<T, const N: u8, const M: usize> From<Vec<T>> for ByteTerminatedVec<T, N, M> {
+    fn from(inner: Vec<T>) -> Self {
+        ByteTerminatedVec { inner }
+    }
+}
+
+impl<T, const N: u8, const M: usize> From<ByteTerminatedVec<T, N, M>> for Vec<T> {
+    fn from(x: ByteTerminatedVec<T, N, M>) -> Self {
         x.inner
     }
 }
 
-impl<T, const N: u8> Deref for ByteTerminatedVec<T, N> {
+impl<T, const N: u8, const M: usize> Deref for ByteTerminatedVec<T, N, M> {
     type Target = Vec<T>;
 
     fn deref(&self) -> &Self::Target {
@@ -123,18 +226,318 @@ impl<T, const N: u8> Deref for ByteTerminatedVec<T, N> {
     }
 }
 
-impl<T, const N: u8> DerefMut for ByteTerminatedVec<T, N> {
+impl<T, const N: u8, const M: usize> DerefMut for ByteTerminatedVec<T, N, M> {
     fn deref_mut(&mut self) -> &mut Self::Target {
         &mut self.inner
     }
 }
 
-impl ReadSelf for CString {
-    fn read_from<B: Read + PositionAware>(_buffer: &mut B) -> io::Result<Self> {
-        // let mut bytes = Vec::new();
-        // buffer.read_until(b'\0', &mut bytes)?;
+impl<T: ReadSelf, const N: u8, const M: usize> ByteTerminatedVec<T, N, M> {
+    /// Equivalent to `read_from`, but takes advantage of the internal buffer of a `BufRead`.
+    pub fn read_buffered<B: BufRead + PositionAware>(buffer: &mut B) -> io::Result<Self> {
+        Self::from_terminated(read_terminated_buffered(buffer, N, M)?)
+    }
+
+    fn from_terminated(bytes: Vec<u8>) -> io::Result<Self> {
+        let mut cursor = Cursor::new(bytes);
+        let inner = read_until_eof(&mut cursor, |tracker| T::read_from(tracker))?;
+        Ok(ByteTerminatedVec { inner })
+    }
+}
+
+impl<T: ReadSelf, const N: u8, const M: usize> ReadSelf for ByteTerminatedVec<T, N, M> {
+    fn read_from<B: Read + PositionAware>(buffer: &mut B) -> io::Result<Self> {
+        Self::from_terminated(read_terminated(buffer, N, M)?)
+    }
+}
+
+impl<T: WriteSelf, const N: u8, const M: usize> WriteSelf for ByteTerminatedVec<T, N, M> {
+    fn write_to<B: Write + PositionAware>(&self, buffer: &mut B) -> io::Result<()> {
+        let mut bytes = Cursor::new(Vec::new());
+        for item in &self.inner {
+            item.write_to(&mut bytes)?;
+        }
+
+        write_terminated(buffer, &bytes.into_inner(), N, M)
+    }
+}
+
+fn write_terminated<B: Write>(
+    buffer: &mut B,
+    bytes: &[u8],
+    terminator: u8,
+    max_len: usize,
+) -> io::Result<()> {
+    if bytes.len() > max_len {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!(
+                "{} bytes exceeds the maximum length of {}",
+                bytes.len(),
+                max_len
+            ),
+        ));
+    }
+
+    if bytes.contains(&terminator) {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "value contains its own terminator",
+        ));
+    }
+
+    buffer.write_all(bytes)?;
+    buffer.write_all(&[terminator])
+}
+
+/// A UTF-8 string followed by a terminating byte. Like `ByteTerminatedVec`, reading fails if the
+/// terminator is not found within `MAX_LEN` bytes, and `read_from` reads one byte at a time while
+/// `read_buffered` searches the buffer of a `BufRead`. Invalid UTF-8 is reported as an
+/// `InvalidData` error with the position of the first invalid byte.
+#[derive(Default, Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TerminatedString<const TERMINATOR: u8 = b'\0', const MAX_LEN: usize = CSTRING_MAX_LEN> {
+    inner: String,
+}
+
+/// A null terminated UTF-8 string.
+pub type NullString<const MAX_LEN: usize = CSTRING_MAX_LEN> = TerminatedString<b'\0', MAX_LEN>;
+
+impl<const N: u8, const M: usize> From<String> for TerminatedString<N, M> {
+    fn from(inner: String) -> Self {
+        TerminatedString { inner }
+    }
+}
+
+impl<const N: u8, const M: usize> From<&str> for TerminatedString<N, M> {
+    fn from(inner: &str) -> Self {
+        TerminatedString {
+            inner: inner.to_owned(),
+        }
+    }
+}
+
+impl<const N: u8, const M: usize> From<TerminatedString<N, M>> for String {
+    fn from(x: TerminatedString<N, M>) -> Self {
+        x.inner
+    }
+}
+
+impl<const N: u8, const M: usize> Deref for TerminatedString<N, M> {
+    type Target = String;
+
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+impl<const N: u8, const M: usize> DerefMut for TerminatedString<N, M> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.inner
+    }
+}
+
+impl<const N: u8, const M: usize> TerminatedString<N, M> {
+    /// Equivalent to `read_from`, but takes advantage of the internal buffer of a `BufRead`.
+    pub fn read_buffered<B: BufRead + PositionAware>(buffer: &mut B) -> io::Result<Self> {
+        let start = buffer.position()?;
+        let inner = utf8_at(read_terminated_buffered(buffer, N, M)?, start)?;
+        Ok(TerminatedString { inner })
+    }
+}
+
+impl<const N: u8, const M: usize> ReadSelf for TerminatedString<N, M> {
+    fn read_from<B: Read + PositionAware>(buffer: &mut B) -> io::Result<Self> {
+        let start = buffer.position()?;
+        let inner = utf8_at(read_terminated(buffer, N, M)?, start)?;
+        Ok(TerminatedString { inner })
+    }
+}
+
+impl<const N: u8, const M: usize> WriteSelf for TerminatedString<N, M> {
+    fn write_to<B: Write + PositionAware>(&self, buffer: &mut B) -> io::Result<()> {
+        write_terminated(buffer, self.inner.as_bytes(), N, M)
+    }
+}
+
+/// Convert bytes read from `position` into a `String`, reporting the position of the first invalid
+/// byte on failure.
+pub(crate) fn utf8_at(bytes: Vec<u8>, position: u64) -> io::Result<String> {
+    String::from_utf8(bytes).map_err(|e| {
+        let offset = position + e.utf8_error().valid_up_to() as u64;
+        Error::new(
+            ErrorKind::InvalidData,
+            format!("invalid UTF-8 at offset {}", offset),
+        )
+    })
+}
+
+/// Rules for how a `FixedStr` is padded when written and trimmed when read.
+pub trait FixedStrRule {
+    /// The byte used to fill the remainder of the field.
+    const PADDING: u8;
+
+    /// Select the portion of the raw field which makes up the string.
+    fn trim(field: &[u8]) -> &[u8];
+}
+
+/// Padded with trailing null bytes. All trailing nulls are removed when reading.
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NullPadded;
+
+impl FixedStrRule for NullPadded {
+    const PADDING: u8 = b'\0';
+
+    fn trim(field: &[u8]) -> &[u8] {
+        let end = field.iter().rposition(|x| *x != b'\0').map_or(0, |x| x + 1);
+        &field[..end]
+    }
+}
+
+/// Padded with trailing nulls, but the string ends at the first null byte. Anything after it is
+/// ignored. This is the layout used by tar headers.
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NullTerminated;
+
+impl FixedStrRule for NullTerminated {
+    const PADDING: u8 = b'\0';
+
+    fn trim(field: &[u8]) -> &[u8] {
+        let end = field
+            .iter()
+            .position(|x| *x == b'\0')
+            .unwrap_or(field.len());
+        &field[..end]
+    }
+}
+
+/// Padded with trailing spaces. All trailing spaces are removed when reading (Ex: FAT file names).
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SpacePadded;
+
+impl FixedStrRule for SpacePadded {
+    const PADDING: u8 = b' ';
+
+    fn trim(field: &[u8]) -> &[u8] {
+        let end = field.iter().rposition(|x| *x != b' ').map_or(0, |x| x + 1);
+        &field[..end]
+    }
+}
+
+/// Padded with trailing spaces. Leading and trailing spaces, along with any trailing nulls, are
+/// removed when reading (Ex: DICOM string values).
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SpaceTrimmed;
+
+impl FixedStrRule for SpaceTrimmed {
+    const PADDING: u8 = b' ';
+
+    fn trim(field: &[u8]) -> &[u8] {
+        let start = field.iter().position(|x| *x != b' ').unwrap_or(field.len());
+        let end = field
+            .iter()
+            .rposition(|x| *x != b' ' && *x != b'\0')
+            .map_or(start, |x| x + 1);
+        &field[start..end]
+    }
+}
+
+/// A UTF-8 string stored in a field of exactly `N` bytes. The rule `R` determines how the field is
+/// padded and trimmed. Writing a string longer than `N` bytes fails with an `InvalidInput` error.
+#[derive(Default, Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FixedStr<const N: usize, R = NullPadded> {
+    inner: String,
+    _rule: PhantomData<R>,
+}
+
+impl<const N: usize, R> From<String> for FixedStr<N, R> {
+    fn from(inner: String) -> Self {
+        FixedStr {
+            inner,
+            _rule: PhantomData,
+        }
+    }
+}
+
+impl<const N: usize, R> From<&str> for FixedStr<N, R> {
+    fn from(inner: &str) -> Self {
+        Self::from(inner.to_owned())
+    }
+}
+
+impl<const N: usize, R> From<FixedStr<N, R>> for String {
+    fn from(x: FixedStr<N, R>) -> Self {
+        x.inner
+    }
+}
+
+impl<const N: usize, R> Deref for FixedStr<N, R> {
+    type Target = String;
+
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+impl<const N: usize, R> DerefMut for FixedStr<N, R> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.inner
+    }
+}
+
+impl<const N: usize, R: FixedStrRule> ReadSelf for FixedStr<N, R> {
+    fn read_from<B: Read + PositionAware>(buffer: &mut B) -> io::Result<Self> {
+        let start = buffer.position()?;
+        let mut field = [0u8; N];
+        buffer.read_exact(&mut field)?;
+
+        // Adjust the position so errors refer to the position in the stream
+        let trimmed = R::trim(&field);
+        let offset = trimmed.as_ptr() as usize - field.as_ptr() as usize;
+        let inner = utf8_at(trimmed.to_vec(), start + offset as u64)?;
+        Ok(Self::from(inner))
+    }
+}
+
+impl<const N: usize, R: FixedStrRule> WriteSelf for FixedStr<N, R> {
+    fn write_to<B: Write + PositionAware>(&self, buffer: &mut B) -> io::Result<()> {
+        let bytes = self.inner.as_bytes();
+        if bytes.len() > N {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "{} bytes does not fit in a field of {} bytes",
+                    bytes.len(),
+                    N
+                ),
+            ));
+        }
 
-        todo!()
+        buffer.write_all(bytes)?;
+        write_padding(buffer, R::PADDING, N - bytes.len())
+    }
+}
+
+/// The maximum length of a `CString` read through `ReadSelf`, excluding the terminator. Like
+/// `TerminatedString`, reading fails with an `InvalidData` error if the terminator is not found
+/// within this many bytes. Use `NullString` or `ByteTerminatedVec` for a different limit. This is
+/// also the default `MAX_LEN` of those types, and the default `max_len` of derived
+/// `null_terminated` string fields.
+pub const CSTRING_MAX_LEN: usize = u16::MAX as usize;
+
+/// Equivalent to `CString::read_from`, but takes advantage of the internal buffer of a `BufRead`.
+/// Like `TerminatedString::read_buffered`, this has to be called explicitly since `ReadSelf` only
+/// requires `Read`.
+pub fn read_cstring_buffered<B: BufRead + PositionAware>(buffer: &mut B) -> io::Result<CString> {
+    let bytes = read_terminated_buffered(buffer, b'\0', CSTRING_MAX_LEN)?;
+    CString::new(bytes).map_err(|e| Error::new(ErrorKind::InvalidData, e))
+}
+
+/// Reads a null terminated string of up to `CSTRING_MAX_LEN` bytes, one byte at a time. Use
+/// `read_cstring_buffered` to search the buffer of a `BufRead` instead.
+impl ReadSelf for CString {
+    fn read_from<B: Read + PositionAware>(buffer: &mut B) -> io::Result<Self> {
+        let bytes = read_terminated(buffer, b'\0', CSTRING_MAX_LEN)?;
+        CString::new(bytes).map_err(|e| Error::new(ErrorKind::InvalidData, e))
     }
 }
 
@@ -144,8 +547,444 @@ impl WriteSelf for CString {
     }
 }
 
-impl<'a> WriteSelf for &'a CStr {
+impl WriteSelf for &CStr {
     fn write_to<B: Write + PositionAware>(&self, buffer: &mut B) -> io::Result<()> {
         buffer.write_all(self.to_bytes_with_nul())
     }
 }
+
+/// The decoding progress of a variable length integer which is read one byte at a time.
+#[derive(Default, Debug, Copy, Clone)]
+pub struct VarIntState {
+    value: u64,
+    count: u32,
+    last: u8,
+}
+
+fn varint_overflow(bits: u32) -> Error {
+    Error::new(
+        ErrorKind::InvalidData,
+        format!("varint does not fit in {} bits", bits),
+    )
+}
+
+fn varint_overlong() -> Error {
+    Error::new(ErrorKind::InvalidData, "overlong varint encoding")
+}
+
+/// An encoding for unsigned variable length integers.
+pub trait UnsignedVarInt {
+    /// Add the next byte to `state`, returning the value once the final byte has been read. An
+    /// error is returned if the value does not fit in `bits` bits or uses more bytes than needed.
+    fn push(state: &mut VarIntState, byte: u8, bits: u32) -> io::Result<Option<u64>>;
+
+    /// Encode `value`, returning the buffer and the number of bytes used.
+    fn encode(value: u64) -> ([u8; 10], usize);
+}
+
+/// An encoding for signed variable length integers.
+pub trait SignedVarInt {
+    /// Add the next byte to `state`, returning the value once the final byte has been read.
+    fn push(state: &mut VarIntState, byte: u8) -> io::Result<Option<i64>>;
+
+    /// Encode `value`, returning the buffer and the number of bytes used.
+    fn encode(value: i64) -> ([u8; 10], usize);
+}
+
+/// Unsigned LEB128, where groups of 7 bits are stored least significant first (Ex: protobuf, WASM,
+/// DWARF).
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Leb128;
+
+impl UnsignedVarInt for Leb128 {
+    fn push(state: &mut VarIntState, byte: u8, bits: u32) -> io::Result<Option<u64>> {
+        let payload = (byte & 0x7F) as u64;
+        let shift = state.count * 7;
+        state.count += 1;
+
+        if shift >= bits || (bits - shift < 7 && payload >> (bits - shift) != 0) {
+            return Err(varint_overflow(bits));
+        }
+        state.value |= payload << shift;
+
+        if byte & 0x80 != 0 {
+            return Ok(None);
+        }
+
+        if state.count > 1 && byte == 0 {
+            return Err(varint_overlong());
+        }
+        Ok(Some(state.value))
+    }
+
+    fn encode(mut value: u64) -> ([u8; 10], usize) {
+        let mut bytes = [0u8; 10];
+        let mut len = 0;
+
+        loop {
+            let byte = (value & 0x7F) as u8;
+            value >>= 7;
+
+            if value == 0 {
+                bytes[len] = byte;
+                return (bytes, len + 1);
+            }
+            bytes[len] = byte | 0x80;
+            len += 1;
+        }
+    }
+}
+
+/// Big endian variable length quantities, where groups of 7 bits are stored most significant first
+/// (Ex: MIDI).
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Vlq;
+
+impl UnsignedVarInt for Vlq {
+    fn push(state: &mut VarIntState, byte: u8, bits: u32) -> io::Result<Option<u64>> {
+        if state.count == 0 && byte == 0x80 {
+            return Err(varint_overlong());
+        }
+        state.count += 1;
+
+        if state.value >> (bits - 7) != 0 {
+            return Err(varint_overflow(bits));
+        }
+        state.value = (state.value << 7) | (byte & 0x7F) as u64;
+
+        match byte & 0x80 {
+            0 => Ok(Some(state.value)),
+            _ => Ok(None),
+        }
+    }
+
+    fn encode(value: u64) -> ([u8; 10], usize) {
+        let groups = (64 - value.leading_zeros()).div_ceil(7).max(1) as usize;
+        let mut bytes = [0u8; 10];
+
+        for (idx, byte) in bytes[..groups].iter_mut().enumerate() {
+            let shift = 7 * (groups - idx - 1);
+            *byte = ((value >> shift) & 0x7F) as u8 | 0x80;
+        }
+        bytes[groups - 1] &= 0x7F;
+        (bytes, groups)
+    }
+}
+
+/// Signed LEB128, where the final group is sign extended (Ex: WASM, DWARF).
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Sleb128;
+
+impl SignedVarInt for Sleb128 {
+    fn push(state: &mut VarIntState, byte: u8) -> io::Result<Option<i64>> {
+        let payload = (byte & 0x7F) as u64;
+        let shift = state.count * 7;
+        state.count += 1;
+
+        if shift >= 64 {
+            return Err(varint_overflow(64));
+        }
+
+        // The bits which do not fit must all be copies of the sign bit
+        if shift + 7 > 64 {
+            let high = payload >> (63 - shift);
+            if high != 0 && high != 0x7F >> (63 - shift) {
+                return Err(varint_overflow(64));
+            }
+        }
+        state.value |= payload << shift;
+
+        let previous = std::mem::replace(&mut state.last, byte);
+        if byte & 0x80 != 0 {
+            return Ok(None);
+        }
+
+        let redundant = match byte {
+            0x00 => previous & 0x40 == 0,
+            0x7F => previous & 0x40 != 0,
+            _ => false,
+        };
+        if state.count > 1 && redundant {
+            return Err(varint_overlong());
+        }
+
+        if shift + 7 < 64 && byte & 0x40 != 0 {
+            state.value |= u64::MAX << (shift + 7);
+        }
+        Ok(Some(state.value as i64))
+    }
+
+    fn encode(mut value: i64) -> ([u8; 10], usize) {
+        let mut bytes = [0u8; 10];
+        let mut len = 0;
+
+        loop {
+            let byte = (value & 0x7F) as u8;
+            value >>= 7;
+
+            let done = (value == 0 && byte & 0x40 == 0) || (value == -1 && byte & 0x40 != 0);
+            if done {
+                bytes[len] = byte;
+                return (bytes, len + 1);
+            }
+            bytes[len] = byte | 0x80;
+            len += 1;
+        }
+    }
+}
+
+/// Zigzag encoding on top of unsigned LEB128 so values close to zero use few bytes regardless of
+/// sign (Ex: protobuf `sint64`).
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ZigZag;
+
+impl SignedVarInt for ZigZag {
+    fn push(state: &mut VarIntState, byte: u8) -> io::Result<Option<i64>> {
+        let value = Leb128::push(state, byte, 64)?;
+        Ok(value.map(|x| (x >> 1) as i64 ^ -((x & 1) as i64)))
+    }
+
+    fn encode(value: i64) -> ([u8; 10], usize) {
+        Leb128::encode(((value << 1) ^ (value >> 63)) as u64)
+    }
+}
+
+macro_rules! impl_var_int {
+    ($(#[$meta:meta])* $name:ident<$format:ident: $kind:ident = $default:ty>($inner:ty), $push:expr) => {
+        $(#[$meta])*
+        #[derive(Default, Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+        pub struct $name<$format = $default> {
+            value: $inner,
+            _format: PhantomData<fn() -> $format>,
+        }
+
+        impl<$format> $name<$format> {
+            pub fn new(value: $inner) -> Self {
+                $name {
+                    value,
+                    _format: PhantomData,
+                }
+            }
+
+            pub fn get(self) -> $inner {
+                self.value
+            }
+        }
+
+        impl<$format> From<$inner> for $name<$format> {
+            fn from(value: $inner) -> Self {
+                Self::new(value)
+            }
+        }
+
+        impl<$format> From<$name<$format>> for $inner {
+            fn from(x: $name<$format>) -> Self {
+                x.value
+            }
+        }
+
+        impl<$format> Deref for $name<$format> {
+            type Target = $inner;
+
+            fn deref(&self) -> &Self::Target {
+                &self.value
+            }
+        }
+
+        impl<$format> crate::TagValue for $name<$format> {
+            type Value = $inner;
+
+            fn into_value(self) -> $inner {
+                self.value
+            }
+
+            fn from_value(value: $inner) -> Self {
+                Self::new(value)
+            }
+        }
+
+        impl<$format: $kind> ReadSelf for $name<$format> {
+            fn read_from<B: Read + PositionAware>(buffer: &mut B) -> io::Result<Self> {
+                let mut state = VarIntState::default();
+                let mut byte = [0u8];
+
+                loop {
+                    buffer.read_exact(&mut byte)?;
+                    if let Some(value) = $push(&mut state, byte[0])? {
+                        return Ok(Self::new(value as $inner));
+                    }
+                }
+            }
+        }
+
+        impl<$format: $kind> WriteSelf for $name<$format> {
+            fn write_to<B: Write + PositionAware>(&self, buffer: &mut B) -> io::Result<()> {
+                let (bytes, len) = $format::encode(self.value as _);
+                buffer.write_all(&bytes[..len])
+            }
+        }
+
+        #[cfg(feature = "tokio")]
+        impl<$format: $kind> AsyncReadSelf for $name<$format> {
+            async fn read_from_async<B>(buffer: &mut B) -> io::Result<Self>
+            where
+                B: AsyncRead + PositionAware + Unpin + Send,
+            {
+                let mut state = VarIntState::default();
+
+                loop {
+                    let byte = buffer.read_u8().await?;
+                    if let Some(value) = $push(&mut state, byte)? {
+                        return Ok(Self::new(value as $inner));
+                    }
+                }
+            }
+        }
+
+        #[cfg(feature = "tokio")]
+        impl<$format: $kind> AsyncWriteSelf for $name<$format> {
+            async fn write_to_async<B>(&self, buffer: &mut B) -> io::Result<()>
+            where
+                B: AsyncWrite + PositionAware + Unpin + Send,
+            {
+                let (bytes, len) = $format::encode(self.value as _);
+                buffer.write_all(&bytes[..len]).await
+            }
+        }
+    };
+}
+
+/// Allow unsigned varints to be used as length prefixes.
+macro_rules! impl_var_int_length {
+    ($name:ident($inner:ty)) => {
+        impl<F> TryFrom<usize> for $name<F> {
+            type Error = <$inner as TryFrom<usize>>::Error;
+
+            fn try_from(value: usize) -> Result<Self, Self::Error> {
+                <$inner>::try_from(value).map(Self::new)
+            }
+        }
+
+        impl<F> TryFrom<$name<F>> for usize {
+            type Error = <usize as TryFrom<$inner>>::Error;
+
+            fn try_from(value: $name<F>) -> Result<Self, Self::Error> {
+                usize::try_from(value.value)
+            }
+        }
+    };
+}
+
+impl_var_int! {
+    /// A `u32` stored as an unsigned variable length integer. Values which would not fit in a
+    /// `u32` or which use more bytes than necessary are rejected when reading.
+    VarU32<F: UnsignedVarInt = Leb128>(u32), |state, byte| F::push(state, byte, 32)
+}
+
+impl_var_int! {
+    /// A `u64` stored as an unsigned variable length integer.
+    VarU64<F: UnsignedVarInt = Leb128>(u64), |state, byte| F::push(state, byte, 64)
+}
+
+impl_var_int! {
+    /// An `i64` stored as a signed variable length integer.
+    VarI64<F: SignedVarInt = Sleb128>(i64), F::push
+}
+
+impl_var_int_length!(VarU32(u32));
+impl_var_int_length!(VarU64(u64));
+
+macro_rules! impl_odd_width {
+    ($(#[$meta:meta])* $name:ident($inner:ty, $wide:ty): $bits:literal, $min:expr, $max:expr) => {
+        $(#[$meta])*
+        #[derive(Default, Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+        pub struct $name(pub $inner);
+
+        impl $name {
+            pub const BITS: u32 = $bits;
+            pub const MIN: $inner = $min;
+            pub const MAX: $inner = $max;
+
+            /// The value widened for writing, or an `InvalidInput` error if it does not fit.
+            pub(crate) fn checked(self) -> io::Result<$wide> {
+                match self.0 {
+                    Self::MIN..=Self::MAX => Ok(self.0 as $wide),
+                    x => Err(Error::new(
+                        ErrorKind::InvalidInput,
+                        format!("{} does not fit in {} bits", x, $bits),
+                    )),
+                }
+            }
+        }
+
+        impl From<$name> for $inner {
+            fn from(x: $name) -> Self {
+                x.0
+            }
+        }
+
+        impl TryFrom<$inner> for $name {
+            type Error = io::Error;
+
+            fn try_from(value: $inner) -> Result<Self, Self::Error> {
+                $name(value).checked().map(|_| $name(value))
+            }
+        }
+
+        impl TryFrom<usize> for $name {
+            type Error = io::Error;
+
+            fn try_from(value: usize) -> Result<Self, Self::Error> {
+                let value = <$inner>::try_from(value)
+                    .map_err(|e| Error::new(ErrorKind::InvalidInput, e))?;
+                Self::try_from(value)
+            }
+        }
+
+        impl TryFrom<$name> for usize {
+            type Error = <usize as TryFrom<$inner>>::Error;
+
+            fn try_from(value: $name) -> Result<Self, Self::Error> {
+                usize::try_from(value.0)
+            }
+        }
+
+        impl crate::TagValue for $name {
+            type Value = $inner;
+
+            fn into_value(self) -> $inner {
+                self.0
+            }
+
+            fn from_value(value: $inner) -> Self {
+                $name(value)
+            }
+        }
+    };
+}
+
+impl_odd_width! {
+    /// A 24-bit unsigned integer (Ex: TLS handshake lengths). Like the other integer types, this
+    /// can only be read and written with an endianness.
+    U24(u32, u64): 24, 0, (1 << 24) - 1
+}
+
+impl_odd_width! {
+    /// A 24-bit signed integer which is sign extended when read (Ex: 24-bit PCM samples).
+    I24(i32, i64): 24, -(1 << 23), (1 << 23) - 1
+}
+
+impl_odd_width! {
+    /// A 40-bit unsigned integer.
+    U40(u64, u64): 40, 0, (1 << 40) - 1
+}
+
+impl_odd_width! {
+    /// A 48-bit unsigned integer (Ex: MAC addresses and counters).
+    U48(u64, u64): 48, 0, (1 << 48) - 1
+}
+
+impl_odd_width! {
+    /// A 56-bit unsigned integer.
+    U56(u64, u64): 56, 0, (1 << 56) - 1
+}
@@ -1,5 +1,7 @@
+use crate::pointer::PointerLayout;
+use crate::{PositionAware, ReadSelf};
 use smallvec::{Array, SmallVec};
-use std::io::{self, ErrorKind, Read, Write};
+use std::io::{self, BufRead, Error, ErrorKind, Read, Write};
 use std::marker::PhantomData;
 
 /// When creating a `std::io::Read` wrapper that performs any form of processing, you can quickly
@@ -185,6 +187,206 @@ impl<R: Read> Read for ByteTerminatedReader<R> {
     }
 }
 
+/// Read bytes until `terminator` is reached, returning the bytes before it. The terminator is
+/// consumed, but not included in the result. Bytes are read one at a time so nothing past the
+/// terminator is consumed from the reader. If the reader is buffered, prefer
+/// `read_terminated_buffered`.
+///
+/// If the terminator is not found within `max_len` bytes, an `InvalidData` error is returned.
+pub fn read_terminated<R: Read>(
+    reader: &mut R,
+    terminator: u8,
+    max_len: usize,
+) -> io::Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    let mut byte = [0u8];
+
+    loop {
+        match reader.read(&mut byte) {
+            Ok(0) => return Err(ErrorKind::UnexpectedEof.into()),
+            Ok(_) if byte[0] == terminator => return Ok(bytes),
+            Ok(_) if bytes.len() >= max_len => return Err(terminator_not_found(max_len)),
+            Ok(_) => bytes.push(byte[0]),
+            Err(ref e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Equivalent to `read_terminated`, but searches the reader's internal buffer directly instead of
+/// reading one byte at a time.
+pub fn read_terminated_buffered<R: BufRead>(
+    reader: &mut R,
+    terminator: u8,
+    max_len: usize,
+) -> io::Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+
+    loop {
+        let available = match reader.fill_buf() {
+            Ok([]) => return Err(ErrorKind::UnexpectedEof.into()),
+            Ok(x) => x,
+            Err(ref e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+
+        match available.iter().position(|x| *x == terminator) {
+            Some(idx) if bytes.len() + idx <= max_len => {
+                bytes.extend_from_slice(&available[..idx]);
+                reader.consume(idx + 1);
+                return Ok(bytes);
+            }
+            _ if bytes.len() + available.len() > max_len => {
+                return Err(terminator_not_found(max_len))
+            }
+            _ => {
+                let len = available.len();
+                bytes.extend_from_slice(available);
+                reader.consume(len);
+            }
+        }
+    }
+}
+
+fn terminator_not_found(max_len: usize) -> Error {
+    Error::new(
+        ErrorKind::InvalidData,
+        format!("terminator not found within {} bytes", max_len),
+    )
+}
+
+/// A reader wrapper which keeps track of whether any bytes have been read. This is used to tell
+/// the difference between a stream which ended cleanly before a value and one which ended part way
+/// through reading it.
+pub struct EofTracker<'a, R> {
+    reader: &'a mut R,
+    consumed: bool,
+}
+
+impl<'a, R> EofTracker<'a, R> {
+    pub fn new(reader: &'a mut R) -> Self {
+        EofTracker {
+            reader,
+            consumed: false,
+        }
+    }
+
+    /// Returns true if at least one byte has been read from the inner reader.
+    pub fn consumed_any(&self) -> bool {
+        self.consumed
+    }
+}
+
+impl<R: Read> Read for EofTracker<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let res = self.reader.read(buf)?;
+        self.consumed |= res > 0;
+        Ok(res)
+    }
+}
+
+impl<R: PositionAware> PositionAware for EofTracker<'_, R> {
+    fn position(&mut self) -> io::Result<u64> {
+        self.reader.position()
+    }
+
+    fn seek_to(&mut self, position: u64) -> io::Result<()> {
+        self.reader.seek_to(position)
+    }
+
+    fn pointer_layout(&mut self) -> Option<&mut dyn PointerLayout> {
+        self.reader.pointer_layout()
+    }
+}
+
+/// Read a value with `reader` unless the stream is already at its end. `Ok(None)` is only returned
+/// if the end of the stream was reached before any bytes were read. See `ReadSelf::try_read_from`.
+pub fn read_unless_eof<B, T, F>(buffer: &mut B, reader: F) -> io::Result<Option<T>>
+where
+    B: Read + PositionAware,
+    F: FnOnce(&mut EofTracker<'_, B>) -> io::Result<T>,
+{
+    let mut tracker = EofTracker::new(buffer);
+
+    match reader(&mut tracker) {
+        Ok(v) => Ok(Some(v)),
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof && !tracker.consumed_any() => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Read values with `reader` until the stream ends cleanly between two values. Since this would
+/// never end for values which do not read any bytes, an error is returned if a value is empty.
+pub fn read_until_eof<B, T, A, F>(buffer: &mut B, mut reader: F) -> io::Result<A>
+where
+    B: Read + PositionAware,
+    A: FromIterator<T>,
+    F: FnMut(&mut EofTracker<'_, B>) -> io::Result<T>,
+{
+    let mut items = Vec::new();
+
+    loop {
+        let start = buffer.position()?;
+        match read_unless_eof(buffer, &mut reader)? {
+            Some(_) if buffer.position()? == start => {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidData,
+                    "unable to read empty values until the end of the stream",
+                ))
+            }
+            Some(item) => items.push(item),
+            None => return Ok(items.into_iter().collect()),
+        }
+    }
+}
+
+/// An iterator which reads consecutive records of `T` until the end of the stream is reached on a
+/// record boundary. If the stream ends part way through a record, the error is yielded and
+/// iteration stops. See `ReadSelf::iter_from`.
+pub struct RecordIter<T, B> {
+    buffer: B,
+    finished: bool,
+    _phantom: PhantomData<fn() -> T>,
+}
+
+impl<T, B> RecordIter<T, B> {
+    pub fn new(buffer: B) -> Self {
+        RecordIter {
+            buffer,
+            finished: false,
+            _phantom: PhantomData,
+        }
+    }
+
+    pub fn into_inner(self) -> B {
+        self.buffer
+    }
+}
+
+impl<T: ReadSelf, B: Read + PositionAware> Iterator for RecordIter<T, B> {
+    type Item = io::Result<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+
+        match T::try_read_from(&mut self.buffer) {
+            Ok(Some(v)) => Some(Ok(v)),
+            Ok(None) => {
+                self.finished = true;
+                None
+            }
+            Err(e) => {
+                self.finished = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+impl<T: ReadSelf, B: Read + PositionAware> std::iter::FusedIterator for RecordIter<T, B> {}
+
 pub trait AbortingFromIterator<T, E>: Iterator<Item = Result<T, E>> {
     fn aborting_from_iter<F: FromIterator<T>>(self) -> Result<F, E>;
 }
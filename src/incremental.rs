@@ -0,0 +1,130 @@
+//! A sans-IO decoder for cases where data arrives in pieces, such as when reading from a
+//! non-blocking socket. Bytes are fed into the decoder as they become available and are only
+//! consumed once a complete value has been decoded.
+use crate::{PositionAware, ReadSelf};
+use std::io::{self, ErrorKind, Read};
+use std::marker::PhantomData;
+
+/// The result of attempting to decode a value from the bytes buffered so far.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decoded<T> {
+    /// A complete value was decoded and the bytes it occupied were consumed.
+    Value(T),
+    /// More data is required before a value can be decoded. The count is a lower bound on the
+    /// number of additional bytes needed, since a value may continue reading after the point where
+    /// it previously ran out of data.
+    NeedMore(usize),
+}
+
+/// Buffers partially received data and decodes values of `T` once enough bytes are available.
+///
+/// Decoding is attempted from the start of the buffered data on every call to `decode`. If the
+/// reader runs out of data part way through, the buffered bytes are kept as-is so no input is lost
+/// and the attempt can be repeated after more data has been fed in. Positions reported through
+/// `PositionAware` are relative to the first byte ever fed to the decoder.
+pub struct IncrementalDecoder<T> {
+    buffer: Vec<u8>,
+    index: usize,
+    position: u64,
+    _phantom: PhantomData<fn() -> T>,
+}
+
+impl<T> Default for IncrementalDecoder<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> IncrementalDecoder<T> {
+    pub fn new() -> Self {
+        IncrementalDecoder {
+            buffer: Vec::new(),
+            index: 0,
+            position: 0,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Append newly received bytes to the end of the buffered data.
+    pub fn feed(&mut self, bytes: &[u8]) {
+        // Reclaim space taken by consumed values before growing the buffer
+        if self.index > 0 && self.index == self.buffer.len() {
+            self.buffer.clear();
+            self.index = 0;
+        } else if self.index > self.buffer.capacity() / 2 {
+            self.buffer.drain(..self.index);
+            self.index = 0;
+        }
+
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    /// The bytes which have been fed to the decoder, but not yet consumed by a decoded value.
+    pub fn buffered(&self) -> &[u8] {
+        &self.buffer[self.index..]
+    }
+
+    /// Consume the decoder and return any bytes which have not yet been decoded.
+    pub fn into_inner(mut self) -> Vec<u8> {
+        self.buffer.drain(..self.index);
+        self.buffer
+    }
+}
+
+impl<T: ReadSelf> IncrementalDecoder<T> {
+    /// Attempt to decode a value from the buffered bytes. Errors other than running out of data
+    /// are returned as-is and leave the buffered data untouched.
+    pub fn decode(&mut self) -> io::Result<Decoded<T>> {
+        let mut reader = PartialReader {
+            bytes: &self.buffer[self.index..],
+            consumed: 0,
+            position: self.position,
+            missing: None,
+        };
+
+        match T::read_from(&mut reader) {
+            Ok(value) => {
+                let consumed = reader.consumed;
+                self.index += consumed;
+                self.position += consumed as u64;
+                Ok(Decoded::Value(value))
+            }
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => match reader.missing {
+                Some(missing) => Ok(Decoded::NeedMore(missing)),
+                // The end of the buffered data was never reached, so the error came from somewhere
+                // else (Ex: a length prefixed body which was shorter than expected).
+                None => Err(e),
+            },
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// A reader over the buffered bytes which records how much data was missing when it runs dry.
+struct PartialReader<'a> {
+    bytes: &'a [u8],
+    consumed: usize,
+    position: u64,
+    missing: Option<usize>,
+}
+
+impl Read for PartialReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let remaining = &self.bytes[self.consumed..];
+        let read_len = remaining.len().min(buf.len());
+        buf[..read_len].copy_from_slice(&remaining[..read_len]);
+        self.consumed += read_len;
+
+        if read_len == 0 && !buf.is_empty() && self.missing.is_none() {
+            self.missing = Some(buf.len());
+        }
+
+        Ok(read_len)
+    }
+}
+
+impl PositionAware for PartialReader<'_> {
+    fn position(&mut self) -> io::Result<u64> {
+        Ok(self.position + self.consumed as u64)
+    }
+}
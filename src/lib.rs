@@ -6,14 +6,27 @@ use std::mem::MaybeUninit;
 use std::rc::Rc;
 use std::sync::Arc;
 
+#[cfg(feature = "tokio")]
+pub mod async_io;
+pub mod bits;
+#[cfg(feature = "codec")]
+pub mod codec;
+pub mod encoding;
+pub mod endian;
 pub mod grammar;
 pub mod helper;
+pub mod incremental;
+pub mod pointer;
 pub mod positional;
+pub mod with;
 
 #[doc(hidden)]
 pub mod derive_util;
 
-use helper::AbortingFromIterator;
+#[cfg(feature = "tokio")]
+pub use async_io::{AsyncReadSelf, AsyncWriteSelf};
+pub use endian::{Be, Le};
+use helper::{read_unless_eof, AbortingFromIterator, RecordIter};
 pub use positional::PositionAware;
 
 pub trait ReadSelf: Sized {
@@ -29,10 +42,23 @@ pub trait ReadSelf: Sized {
         count: usize,
     ) -> io::Result<F> {
         (0..count)
-            .into_iter()
             .map(|_| Self::read_from(buffer))
             .aborting_from_iter()
     }
+
+    /// Read a value unless the stream is already at its end. `Ok(None)` is only returned if the
+    /// end of the stream was reached before any bytes were read. Reaching the end part way through
+    /// a value is still an `UnexpectedEof` error.
+    fn try_read_from<B: Read + PositionAware>(buffer: &mut B) -> io::Result<Option<Self>> {
+        read_unless_eof(buffer, |tracker| Self::read_from(tracker))
+    }
+
+    /// Iterate over consecutive values in a stream until it ends cleanly between two values. This
+    /// is intended for formats which are just a series of records with no count. Types which do
+    /// not read any bytes will never reach the end of the stream.
+    fn iter_from<B: Read + PositionAware>(buffer: B) -> RecordIter<Self, B> {
+        RecordIter::new(buffer)
+    }
 }
 
 impl ReadSelf for u8 {
@@ -161,6 +187,13 @@ impl<'a, T: 'a + ReadIntoSelf, I: Iterator<Item = &'a mut T>> ReadIntoSelf for I
 
 pub trait WriteSelf: Sized {
     fn write_to<B: Write + PositionAware>(&self, buffer: &mut B) -> io::Result<()>;
+
+    /// Write the value to a new buffer. This is the counterpart of `ReadSelf::from_bytes`.
+    fn to_bytes(&self) -> io::Result<Vec<u8>> {
+        let mut buffer = Cursor::new(Vec::new());
+        self.write_to(&mut buffer)?;
+        Ok(buffer.into_inner())
+    }
 }
 
 impl WriteSelf for u8 {
@@ -191,24 +224,45 @@ impl WriteSelf for () {
     }
 }
 
-impl<T: ?Sized + WriteSelf> WriteSelf for Box<T> {
+impl<T: WriteSelf> WriteSelf for Box<T> {
     #[inline(always)]
     fn write_to<B: Write + PositionAware>(&self, buffer: &mut B) -> io::Result<()> {
-        T::write_to(&*self, buffer)
+        T::write_to(self, buffer)
     }
 }
 
-impl<T: ?Sized + WriteSelf> WriteSelf for Arc<T> {
+impl<T: WriteSelf> WriteSelf for Arc<T> {
     #[inline(always)]
     fn write_to<B: Write + PositionAware>(&self, buffer: &mut B) -> io::Result<()> {
-        T::write_to(&*self, buffer)
+        T::write_to(self, buffer)
     }
 }
 
-impl<T: ?Sized + WriteSelf> WriteSelf for Rc<T> {
+impl<T: WriteSelf> WriteSelf for Rc<T> {
     #[inline(always)]
     fn write_to<B: Write + PositionAware>(&self, buffer: &mut B) -> io::Result<()> {
-        T::write_to(&*self, buffer)
+        T::write_to(self, buffer)
+    }
+}
+
+impl<T: WriteSelf> WriteSelf for Vec<T> {
+    #[inline(always)]
+    fn write_to<B: Write + PositionAware>(&self, buffer: &mut B) -> io::Result<()> {
+        for item in self {
+            item.write_to(buffer)?;
+        }
+        Ok(())
+    }
+}
+
+/// Nothing is written for `None`.
+impl<T: WriteSelf> WriteSelf for Option<T> {
+    #[inline(always)]
+    fn write_to<B: Write + PositionAware>(&self, buffer: &mut B) -> io::Result<()> {
+        match self {
+            Some(value) => value.write_to(buffer),
+            None => Ok(()),
+        }
     }
 }
 
@@ -221,3 +275,100 @@ impl<T: WriteSelf, const N: usize> WriteSelf for [T; N] {
         Ok(())
     }
 }
+
+/// Converts enum tags to and from a value which can be matched against the `tag` of each variant.
+/// The derived implementations read the tag, then match `into_value` against the variant tags, and
+/// build the tag to write with `from_value`. This lets wrapper types such as `VarU32` or `Be<u16>`
+/// be used as tags while variants still use integer literals.
+///
+/// Any other type used as a tag needs to implement this, usually with `Value = Self` so variants
+/// can be matched by its own constants (Ex: `tag = "Kind::A"`). `tag_value!` provides this impl.
+pub trait TagValue {
+    type Value;
+
+    fn into_value(self) -> Self::Value;
+
+    fn from_value(value: Self::Value) -> Self;
+}
+
+/// Implements `TagValue` with `Value = Self` so a type can be used as the tag of an enum and
+/// matched against its own constants. Generic parameters are given in brackets before the type, as
+/// with `endian_agnostic!`. Array types always need the brackets, even when empty (Ex:
+/// `[] [u8; 4]`), since their own brackets would otherwise be read as the generic parameters.
+///
+/// ```
+/// use io_self::TagValue;
+///
+/// #[derive(Debug, Clone, Copy, PartialEq)]
+/// pub struct Kind(u8);
+///
+/// #[derive(Debug, Clone, Copy, PartialEq)]
+/// pub struct Wrapper<T>(T);
+///
+/// io_self::tag_value!(Kind, [T] Wrapper<T>);
+///
+/// assert_eq!(Kind(3).into_value(), Kind(3));
+/// assert_eq!(Wrapper::from_value(Wrapper(1u8)), Wrapper(1));
+/// ```
+#[macro_export]
+macro_rules! tag_value {
+    () => {};
+    ([$($generics:tt)*] $ty:ty $(, $($rest:tt)*)?) => {
+        impl<$($generics)*> $crate::TagValue for $ty {
+            type Value = Self;
+
+            #[inline(always)]
+            fn into_value(self) -> Self::Value {
+                self
+            }
+
+            #[inline(always)]
+            fn from_value(value: Self::Value) -> Self {
+                value
+            }
+        }
+
+        $crate::tag_value!($($($rest)*)?);
+    };
+    ($ty:ty $(, $($rest:tt)*)?) => {
+        $crate::tag_value!([] $ty $(, $($rest)*)?);
+    };
+}
+
+tag_value! {
+    u8,
+    u16,
+    u32,
+    u64,
+    u128,
+    i8,
+    i16,
+    i32,
+    i64,
+    i128,
+    bool,
+    char,
+    [const N: usize] [u8; N],
+}
+
+/// Tuples of tags let variants be selected by several values at once, such as a class and a code
+/// (Ex: `tag = "(0x02, 0x10)"`).
+macro_rules! impl_tuple_tag_value {
+    ($(($($name:ident: $idx:tt),+)),+) => {
+        $(impl<$($name: TagValue),+> TagValue for ($($name,)+) {
+            type Value = ($($name::Value,)+);
+
+            #[inline(always)]
+            fn into_value(self) -> Self::Value {
+                ($(self.$idx.into_value(),)+)
+            }
+
+            #[inline(always)]
+            fn from_value(value: Self::Value) -> Self {
+                ($($name::from_value(value.$idx),)+)
+            }
+        })+
+    };
+}
+
+impl_tuple_tag_value!((A: 0, B: 1), (A: 0, B: 1, C: 2), (A: 0, B: 1, C: 2, D: 3));
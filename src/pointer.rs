@@ -0,0 +1,434 @@
+//! Offset pointers to data stored elsewhere in a stream, and a writer which lays out their targets.
+use crate::derive_util::{ReadSelfEndian, WriteSelfEndian};
+use crate::grammar::write_padding;
+use crate::{PositionAware, ReadSelf, WriteSelf};
+use std::collections::VecDeque;
+use std::io::{self, Cursor, Error, ErrorKind, Read, Write};
+
+/// An offset of type `P` to a value of `T` stored elsewhere in the stream. Many formats store
+/// tables of offsets (Ex: ELF section headers or TrueType table records) which point to the actual
+/// data.
+///
+/// Reading a `FilePtr` only reads the offset. The target can then be resolved later against a
+/// stream which supports seeking via `PositionAware::seek_to`. Resolving seeks to the target, reads
+/// the value, then returns to the previous position so reading can continue uninterrupted.
+///
+/// The location of the target is the offset added to a base position. By default the base is `0`
+/// so offsets are absolute, but it can be changed with `with_base` or `set_base` for offsets which
+/// are relative to the start of a struct or some other value. When deriving `ReadSelf`, the
+/// `offset_base` attribute can be used to set the base, and `follow` to resolve the target
+/// immediately.
+///
+/// Writing a `FilePtr` which has not been resolved only writes the offset and the target must be
+/// written separately. A resolved target can only be written to a `PointerWriter`, which lays it out
+/// automatically and patches the offset to point to it. Writing a resolved pointer anywhere else is
+/// an `InvalidInput` error rather than silently dropping the target, so use `take` first to only
+/// write the offset.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilePtr<P, T> {
+    offset: P,
+    base: u64,
+    value: Option<T>,
+}
+
+impl<P, T> FilePtr<P, T> {
+    pub fn new(offset: P) -> Self {
+        FilePtr {
+            offset,
+            base: 0,
+            value: None,
+        }
+    }
+
+    pub fn with_base(mut self, base: u64) -> Self {
+        self.base = base;
+        self
+    }
+
+    pub fn set_base(&mut self, base: u64) {
+        self.base = base;
+    }
+
+    /// Set the target value. This is mainly useful for pointers which will be written to a
+    /// `PointerWriter`.
+    pub fn with_value(mut self, value: T) -> Self {
+        self.value = Some(value);
+        self
+    }
+
+    pub fn set_value(&mut self, value: T) {
+        self.value = Some(value);
+    }
+
+    pub fn offset(&self) -> &P {
+        &self.offset
+    }
+
+    pub fn base(&self) -> u64 {
+        self.base
+    }
+
+    pub fn is_resolved(&self) -> bool {
+        self.value.is_some()
+    }
+
+    /// Get the target value if it has already been resolved.
+    pub fn get(&self) -> Option<&T> {
+        self.value.as_ref()
+    }
+
+    pub fn get_mut(&mut self) -> Option<&mut T> {
+        self.value.as_mut()
+    }
+
+    pub fn into_inner(self) -> Option<T> {
+        self.value
+    }
+
+    /// Remove the target value, leaving only the offset.
+    pub fn take(&mut self) -> Option<T> {
+        self.value.take()
+    }
+}
+
+impl<P: Copy + Into<u64>, T> FilePtr<P, T> {
+    /// The position of the target within the stream.
+    pub fn target(&self) -> io::Result<u64> {
+        self.base
+            .checked_add(self.offset.into())
+            .ok_or_else(|| Error::new(ErrorKind::InvalidData, "pointer target overflowed u64"))
+    }
+
+    /// Read the target from the stream if it has not already been resolved.
+    pub fn resolve<B>(&mut self, buffer: &mut B) -> io::Result<&T>
+    where
+        B: Read + PositionAware,
+        T: ReadSelf,
+    {
+        self.resolve_with(buffer, T::read_from)
+    }
+
+    /// Equivalent to `resolve`, but reads the target using a specific byte order `O`.
+    pub fn resolve_endian<O, B>(&mut self, buffer: &mut B) -> io::Result<&T>
+    where
+        B: Read + PositionAware,
+        T: ReadSelfEndian<O>,
+    {
+        self.resolve_with(buffer, <T as ReadSelfEndian<O>>::read_from)
+    }
+
+    fn resolve_with<B, F>(&mut self, buffer: &mut B, reader: F) -> io::Result<&T>
+    where
+        B: Read + PositionAware,
+        F: FnOnce(&mut B) -> io::Result<T>,
+    {
+        if self.value.is_none() {
+            let target = self.target()?;
+            let previous = buffer.position()?;
+
+            buffer.seek_to(target)?;
+            let value = reader(buffer);
+            buffer.seek_to(previous)?;
+
+            self.value = Some(value?);
+        }
+
+        Ok(self.value.as_ref().unwrap())
+    }
+}
+
+impl<P: ReadSelf, T> ReadSelf for FilePtr<P, T> {
+    #[inline(always)]
+    fn read_from<B: Read + PositionAware>(buffer: &mut B) -> io::Result<Self> {
+        Ok(FilePtr::new(P::read_from(buffer)?))
+    }
+}
+
+impl<O, P: ReadSelfEndian<O>, T> ReadSelfEndian<O> for FilePtr<P, T> {
+    #[inline(always)]
+    fn read_from<B: Read + PositionAware>(buffer: &mut B) -> io::Result<Self> {
+        Ok(FilePtr::new(P::read_from(buffer)?))
+    }
+}
+
+impl<P, T> WriteSelf for FilePtr<P, T>
+where
+    P: WriteSelf + TryFrom<u64>,
+    <P as TryFrom<u64>>::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+    T: WriteSelf,
+{
+    fn write_to<B: Write + PositionAware>(&self, buffer: &mut B) -> io::Result<()> {
+        self.write_with(buffer, encode_offset::<P>, T::write_to)?;
+        self.offset.write_to(buffer)
+    }
+}
+
+impl<O, P, T> WriteSelfEndian<O> for FilePtr<P, T>
+where
+    P: WriteSelfEndian<O> + TryFrom<u64>,
+    <P as TryFrom<u64>>::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+    T: WriteSelfEndian<O>,
+{
+    fn write_to<B: Write + PositionAware>(&self, buffer: &mut B) -> io::Result<()> {
+        self.write_with(buffer, encode_offset_endian::<O, P>, T::write_to)?;
+        self.offset.write_to(buffer)
+    }
+}
+
+impl<P, T> FilePtr<P, T> {
+    /// Defers the resolved target of the pointer about to be written, if there is one.
+    fn write_with<B, F>(
+        &self,
+        buffer: &mut B,
+        encode: fn(u64) -> io::Result<Vec<u8>>,
+        write_target: F,
+    ) -> io::Result<()>
+    where
+        B: Write + PositionAware,
+        F: FnOnce(&T, &mut PointerWriter<Cursor<Vec<u8>>>) -> io::Result<()>,
+    {
+        let Some(value) = &self.value else {
+            return Ok(());
+        };
+
+        let placeholder = buffer.position()?;
+        let Some(layout) = buffer.pointer_layout() else {
+            return Err(unplaced_target());
+        };
+
+        // Write the target to a separate buffer so it can be placed once the layout of everything
+        // else is known. Any pointers within the target get their own table.
+        let mut target = PointerWriter::new(Cursor::new(Vec::new()));
+        write_target(value, &mut target)?;
+
+        layout.pointer_table().entries.push(DeferredPointer {
+            placeholder,
+            base: self.base,
+            encode,
+            width: encode(0)?.len(),
+            target: target.writer.into_inner(),
+            children: target.table.entries,
+        });
+        Ok(())
+    }
+}
+
+fn unplaced_target() -> Error {
+    Error::new(
+        ErrorKind::InvalidInput,
+        "the target of a resolved pointer can only be written to a PointerWriter",
+    )
+}
+
+fn encode_offset<P>(offset: u64) -> io::Result<Vec<u8>>
+where
+    P: WriteSelf + TryFrom<u64>,
+    <P as TryFrom<u64>>::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    let offset = P::try_from(offset).map_err(Error::other)?;
+    let mut bytes = Cursor::new(Vec::new());
+    offset.write_to(&mut bytes)?;
+    Ok(bytes.into_inner())
+}
+
+fn encode_offset_endian<O, P>(offset: u64) -> io::Result<Vec<u8>>
+where
+    P: WriteSelfEndian<O> + TryFrom<u64>,
+    <P as TryFrom<u64>>::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    let offset = P::try_from(offset).map_err(Error::other)?;
+    let mut bytes = Cursor::new(Vec::new());
+    offset.write_to(&mut bytes)?;
+    Ok(bytes.into_inner())
+}
+
+/// A pointer written to a `PointerWriter` whose target has not been placed yet.
+struct DeferredPointer {
+    /// The position of the offset which needs to be patched.
+    placeholder: u64,
+    base: u64,
+    encode: fn(u64) -> io::Result<Vec<u8>>,
+    width: usize,
+    target: Vec<u8>,
+    /// Pointers within the target with placeholders relative to the start of the target.
+    children: Vec<DeferredPointer>,
+}
+
+/// The pointers written to a `PointerWriter` which are waiting to be patched. This is only exposed
+/// through `PointerLayout`.
+#[derive(Default)]
+pub struct PointerTable {
+    entries: Vec<DeferredPointer>,
+}
+
+/// Writers which lay out the targets of the pointers written to them. A `FilePtr` reaches this
+/// through the stream it is written to, which only `PointerWriter` provides.
+pub trait PointerLayout {
+    fn pointer_table(&mut self) -> &mut PointerTable;
+}
+
+/// The pointers deferred while buffering part of a stream (Ex: a length prefixed body), which need
+/// to be moved to the stream the buffer is eventually written to.
+#[doc(hidden)]
+pub struct DeferredTargets(Vec<DeferredPointer>);
+
+impl DeferredTargets {
+    /// Moves the pointers to the layout of `buffer`, which is positioned where the buffered bytes
+    /// are about to be written.
+    pub fn forward_to<B: PositionAware>(self, buffer: &mut B) -> io::Result<()> {
+        if self.0.is_empty() {
+            return Ok(());
+        }
+
+        let position = buffer.position()?;
+        let layout = buffer.pointer_layout().ok_or_else(unplaced_target)?;
+        layout
+            .pointer_table()
+            .entries
+            .extend(self.0.into_iter().map(|mut entry| {
+                entry.placeholder += position;
+                entry
+            }));
+        Ok(())
+    }
+}
+
+/// A writer which automatically lays out the targets of any resolved `FilePtr` written to it.
+///
+/// Each pointer is written with a placeholder offset and its target is buffered. When `finish` is
+/// called, the targets are appended to the end of the stream in the order their pointers were
+/// written (followed by any pointers within those targets), then each placeholder is patched with
+/// the offset to its target. Targets are aligned to the configured alignment, which defaults to 1.
+///
+/// Patching requires the inner writer to support `PositionAware::seek_to`. Offsets are computed
+/// relative to the base stored in each pointer, so pointers which are not absolute need their base
+/// set before being written.
+pub struct PointerWriter<W> {
+    writer: W,
+    alignment: u64,
+    padding_byte: u8,
+    table: PointerTable,
+}
+
+impl<W: Write + PositionAware> PointerWriter<W> {
+    pub fn new(writer: W) -> Self {
+        PointerWriter {
+            writer,
+            alignment: 1,
+            padding_byte: 0,
+            table: PointerTable::default(),
+        }
+    }
+
+    /// Align the start of each target to a multiple of `alignment`. Like `PadToAlign`, any
+    /// alignment is allowed and an alignment of 0 is ignored.
+    pub fn with_alignment(mut self, alignment: u64) -> Self {
+        self.alignment = alignment;
+        self
+    }
+
+    pub fn with_padding_byte(mut self, padding_byte: u8) -> Self {
+        self.padding_byte = padding_byte;
+        self
+    }
+
+    /// Write the targets of all pointers written so far and patch their offsets. The stream is left
+    /// positioned after the last target.
+    pub fn finish(mut self) -> io::Result<W> {
+        let mut position = self.writer.position()?;
+        let mut patches = Vec::new();
+
+        let mut queue = self
+            .table
+            .entries
+            .drain(..)
+            .map(|entry| (0, entry))
+            .collect::<VecDeque<_>>();
+
+        while let Some((parent, entry)) = queue.pop_front() {
+            let padding = padding_for(position, self.alignment);
+            write_padding(&mut self.writer, self.padding_byte, padding as usize)?;
+            position += padding;
+
+            self.writer.write_all(&entry.target)?;
+            let offset = position.checked_sub(entry.base).ok_or_else(|| {
+                Error::new(
+                    ErrorKind::InvalidData,
+                    "pointer target is located before its base",
+                )
+            })?;
+
+            let encoded = (entry.encode)(offset)?;
+            if encoded.len() != entry.width {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    "patched offset has a different width than its placeholder",
+                ));
+            }
+
+            patches.push((parent + entry.placeholder, encoded));
+            queue.extend(entry.children.into_iter().map(|child| (position, child)));
+            position += entry.target.len() as u64;
+        }
+
+        for (placeholder, encoded) in patches {
+            self.writer.seek_to(placeholder)?;
+            self.writer.write_all(&encoded)?;
+        }
+
+        self.writer.seek_to(position)?;
+        Ok(self.writer)
+    }
+}
+
+impl PointerWriter<Cursor<Vec<u8>>> {
+    /// Split a buffered section into its bytes and the pointers whose targets have not been placed.
+    #[doc(hidden)]
+    pub fn into_section(self) -> (Vec<u8>, DeferredTargets) {
+        (
+            self.writer.into_inner(),
+            DeferredTargets(self.table.entries),
+        )
+    }
+}
+
+fn padding_for(position: u64, alignment: u64) -> u64 {
+    if alignment < 2 {
+        return 0;
+    }
+
+    match position % alignment {
+        0 => 0,
+        offset => alignment - offset,
+    }
+}
+
+impl<W: Write> Write for PointerWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.writer.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+impl<W: PositionAware> PositionAware for PointerWriter<W> {
+    fn position(&mut self) -> io::Result<u64> {
+        self.writer.position()
+    }
+
+    fn seek_to(&mut self, position: u64) -> io::Result<()> {
+        self.writer.seek_to(position)
+    }
+
+    fn pointer_layout(&mut self) -> Option<&mut dyn PointerLayout> {
+        Some(self)
+    }
+}
+
+impl<W> PointerLayout for PointerWriter<W> {
+    fn pointer_table(&mut self) -> &mut PointerTable {
+        &mut self.table
+    }
+}
@@ -1,16 +1,45 @@
-use std::io::{self, Read, Seek, Write};
+use crate::pointer::PointerLayout;
+use std::io::{self, BufRead, ErrorKind, Read, Seek, SeekFrom, Write};
+#[cfg(feature = "tokio")]
+use std::pin::Pin;
+#[cfg(feature = "tokio")]
+use std::task::{Context, Poll};
+#[cfg(feature = "tokio")]
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
 
 /// This trait allows access to the current position of a stream. Essentially, it limits the
 /// functionality of `std::io::Seek` to `stream_position`. This trait is helpful for cases where
 /// knowing the offset from the start of a stream is important, but the cursor will never be moved.
+///
+/// Streams which are able to move their cursor can also override `seek_to`. This is only used in
+/// the few cases which require jumping to another location in the stream, such as following a
+/// `FilePtr`.
 pub trait PositionAware {
     fn position(&mut self) -> io::Result<u64>;
+
+    fn seek_to(&mut self, _position: u64) -> io::Result<()> {
+        Err(io::Error::new(
+            ErrorKind::Unsupported,
+            "stream does not support seeking",
+        ))
+    }
+
+    /// Writers which lay out the targets of pointers themselves return their layout here. This is
+    /// overridden by `PointerWriter`, and forwarded by the wrappers in this crate.
+    #[doc(hidden)]
+    fn pointer_layout(&mut self) -> Option<&mut dyn PointerLayout> {
+        None
+    }
 }
 
 impl<S: Seek> PositionAware for S {
     fn position(&mut self) -> io::Result<u64> {
         self.stream_position()
     }
+
+    fn seek_to(&mut self, position: u64) -> io::Result<()> {
+        self.seek(SeekFrom::Start(position)).map(|_| ())
+    }
 }
 
 /// A minimal position aware reader.
@@ -36,6 +65,17 @@ impl<R: Read> Read for ReadCounter<R> {
     }
 }
 
+impl<R: BufRead> BufRead for ReadCounter<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.reader.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.reader.consume(amt);
+        self.position += amt as u64;
+    }
+}
+
 impl<R> PositionAware for ReadCounter<R> {
     fn position(&mut self) -> io::Result<u64> {
         Ok(self.position)
@@ -46,6 +86,14 @@ impl<R> PositionAware for ReadCounter<R> {
 pub struct WriteCounter<W> {
     writer: W,
     position: u64,
+    layout: LayoutHook<W>,
+}
+
+/// Reaches the pointer layout of the inner writer of a counter, if it has one.
+type LayoutHook<W> = fn(&mut W) -> Option<&mut dyn PointerLayout>;
+
+fn no_layout<W>(_: &mut W) -> Option<&mut dyn PointerLayout> {
+    None
 }
 
 impl<W: Write> WriteCounter<W> {
@@ -53,8 +101,26 @@ impl<W: Write> WriteCounter<W> {
         WriteCounter {
             writer,
             position: 0,
+            layout: no_layout,
         }
     }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write + PositionAware> WriteCounter<W> {
+    /// Wraps a writer which already knows its position, such as a `PointerWriter`. Counting
+    /// continues from the position of the writer, and its pointer layout is forwarded so the
+    /// targets of pointers written through the counter are still laid out.
+    pub fn continuing(mut writer: W) -> io::Result<Self> {
+        Ok(WriteCounter {
+            position: writer.position()?,
+            writer,
+            layout: W::pointer_layout,
+        })
+    }
 }
 
 impl<W: Write> Write for WriteCounter<W> {
@@ -73,4 +139,112 @@ impl<W> PositionAware for WriteCounter<W> {
     fn position(&mut self) -> io::Result<u64> {
         Ok(self.position)
     }
+
+    fn pointer_layout(&mut self) -> Option<&mut dyn PointerLayout> {
+        (self.layout)(&mut self.writer)
+    }
+}
+
+/// A minimal position aware asynchronous reader.
+#[cfg(feature = "tokio")]
+pub struct AsyncReadCounter<R> {
+    reader: R,
+    position: u64,
+}
+
+#[cfg(feature = "tokio")]
+impl<R: AsyncRead + Unpin> AsyncReadCounter<R> {
+    pub fn new(reader: R) -> Self {
+        AsyncReadCounter {
+            reader,
+            position: 0,
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<R: AsyncRead + Unpin> AsyncRead for AsyncReadCounter<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let filled = buf.filled().len();
+        let res = Pin::new(&mut self.reader).poll_read(cx, buf);
+        self.position += (buf.filled().len() - filled) as u64;
+        res
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<R> PositionAware for AsyncReadCounter<R> {
+    fn position(&mut self) -> io::Result<u64> {
+        Ok(self.position)
+    }
+}
+
+/// A minimal position aware asynchronous writer.
+#[cfg(feature = "tokio")]
+pub struct AsyncWriteCounter<W> {
+    writer: W,
+    position: u64,
+    layout: LayoutHook<W>,
+}
+
+#[cfg(feature = "tokio")]
+impl<W: AsyncWrite + Unpin> AsyncWriteCounter<W> {
+    pub fn new(writer: W) -> Self {
+        AsyncWriteCounter {
+            writer,
+            position: 0,
+            layout: no_layout,
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<W: AsyncWrite + PositionAware + Unpin> AsyncWriteCounter<W> {
+    /// Wraps a writer which already knows its position, continuing from it and forwarding its
+    /// pointer layout like `WriteCounter::continuing`.
+    pub fn continuing(mut writer: W) -> io::Result<Self> {
+        Ok(AsyncWriteCounter {
+            position: writer.position()?,
+            writer,
+            layout: W::pointer_layout,
+        })
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<W: AsyncWrite + Unpin> AsyncWrite for AsyncWriteCounter<W> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let res = Pin::new(&mut self.writer).poll_write(cx, buf);
+        if let Poll::Ready(Ok(written)) = res {
+            self.position += written as u64;
+        }
+        res
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.writer).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.writer).poll_shutdown(cx)
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<W> PositionAware for AsyncWriteCounter<W> {
+    fn position(&mut self) -> io::Result<u64> {
+        Ok(self.position)
+    }
+
+    fn pointer_layout(&mut self) -> Option<&mut dyn PointerLayout> {
+        (self.layout)(&mut self.writer)
+    }
 }
@@ -0,0 +1,170 @@
+//! Built-in codecs for the `with` field attribute. A codec is a module providing the functions
+//! below, where `T` is the type of the field, `O` is the field's byte order (`()` if none was
+//! given), and `A` is the type of the field's `args` (`()` if none were given):
+//!
+//! ```ignore
+//! pub fn read_from<O, B: Read + PositionAware>(buffer: &mut B, args: A) -> io::Result<T>;
+//! pub fn write_to<O, B: Write + PositionAware>(value: &T, buffer: &mut B, args: A) -> io::Result<()>;
+//! pub fn encoded_len<O>(value: &T, args: A) -> usize;
+//! ```
+//!
+//! `args` may name fields which come before it, and are passed by reference in both directions:
+//! `read_from` receives a reference to the value of `args`, and since fields are borrowed while
+//! writing, `write_to` and `encoded_len` receive a reference to the field. A codec taking
+//! `args = count` where `count: u16` therefore takes `&u16` in all three functions, and fields
+//! which are not `Copy` can be used as args. `encoded_len` is only used when the field also has a
+//! `length_prefix`, in which case the prefix holds the number of bytes written by the codec.
+//!
+//! ```ignore
+//! #[derive(ReadSelf, WriteSelf)]
+//! #[io_self(endian = "big")]
+//! pub struct Lease {
+//!     #[io_self(with = io_self::with::ipv4)]
+//!     address: Ipv4Addr,
+//!     #[io_self(with = io_self::with::unix_timestamp)]
+//!     expires: SystemTime,
+//! }
+//! ```
+
+/// A `SystemTime` stored as the number of whole seconds since the Unix epoch in a `u32`. Times
+/// before the epoch or after 2106 can not be written.
+pub mod unix_timestamp {
+    use crate::PositionAware;
+    use byteorder::{ByteOrder, ReadBytesExt, WriteBytesExt};
+    use std::io::{self, Error, ErrorKind, Read, Write};
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    pub fn read_from<O: ByteOrder, B: Read + PositionAware>(
+        buffer: &mut B,
+        _: (),
+    ) -> io::Result<SystemTime> {
+        let seconds = buffer.read_u32::<O>()?;
+        Ok(UNIX_EPOCH + Duration::from_secs(seconds as u64))
+    }
+
+    pub fn write_to<O: ByteOrder, B: Write + PositionAware>(
+        value: &SystemTime,
+        buffer: &mut B,
+        _: (),
+    ) -> io::Result<()> {
+        let seconds = value
+            .duration_since(UNIX_EPOCH)
+            .ok()
+            .and_then(|duration| u32::try_from(duration.as_secs()).ok())
+            .ok_or_else(|| {
+                Error::new(
+                    ErrorKind::InvalidInput,
+                    "time does not fit in a 32 bit unix timestamp",
+                )
+            })?;
+
+        buffer.write_u32::<O>(seconds)
+    }
+
+    pub fn encoded_len<O>(_: &SystemTime, _: ()) -> usize {
+        4
+    }
+}
+
+/// A `SystemTime` stored as the number of milliseconds since the Unix epoch in an `i64`, which
+/// allows times before the epoch.
+pub mod unix_timestamp_millis {
+    use crate::PositionAware;
+    use byteorder::{ByteOrder, ReadBytesExt, WriteBytesExt};
+    use std::io::{self, Error, ErrorKind, Read, Write};
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    pub fn read_from<O: ByteOrder, B: Read + PositionAware>(
+        buffer: &mut B,
+        _: (),
+    ) -> io::Result<SystemTime> {
+        let millis = buffer.read_i64::<O>()?;
+        let offset = Duration::from_millis(millis.unsigned_abs());
+
+        let time = match millis < 0 {
+            true => UNIX_EPOCH.checked_sub(offset),
+            false => UNIX_EPOCH.checked_add(offset),
+        };
+        time.ok_or_else(|| Error::new(ErrorKind::InvalidData, "timestamp is out of range"))
+    }
+
+    pub fn write_to<O: ByteOrder, B: Write + PositionAware>(
+        value: &SystemTime,
+        buffer: &mut B,
+        _: (),
+    ) -> io::Result<()> {
+        let millis = match value.duration_since(UNIX_EPOCH) {
+            Ok(after) => i64::try_from(after.as_millis()).ok(),
+            Err(before) => i64::try_from(before.duration().as_millis())
+                .ok()
+                .map(|x| -x),
+        };
+        let millis = millis.ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                "time does not fit in a 64 bit unix timestamp",
+            )
+        })?;
+
+        buffer.write_i64::<O>(millis)
+    }
+
+    pub fn encoded_len<O>(_: &SystemTime, _: ()) -> usize {
+        8
+    }
+}
+
+/// An `Ipv4Addr` stored as a `u32`. Addresses are conventionally big endian, but formats which
+/// store them as little endian integers are also supported by the field's endianness.
+pub mod ipv4 {
+    use crate::PositionAware;
+    use byteorder::{ByteOrder, ReadBytesExt, WriteBytesExt};
+    use std::io::{self, Read, Write};
+    use std::net::Ipv4Addr;
+
+    pub fn read_from<O: ByteOrder, B: Read + PositionAware>(
+        buffer: &mut B,
+        _: (),
+    ) -> io::Result<Ipv4Addr> {
+        buffer.read_u32::<O>().map(Ipv4Addr::from)
+    }
+
+    pub fn write_to<O: ByteOrder, B: Write + PositionAware>(
+        value: &Ipv4Addr,
+        buffer: &mut B,
+        _: (),
+    ) -> io::Result<()> {
+        buffer.write_u32::<O>(u32::from(*value))
+    }
+
+    pub fn encoded_len<O>(_: &Ipv4Addr, _: ()) -> usize {
+        4
+    }
+}
+
+/// An `Ipv6Addr` stored as a `u128`, following the field's endianness like [`ipv4`].
+pub mod ipv6 {
+    use crate::PositionAware;
+    use byteorder::{ByteOrder, ReadBytesExt, WriteBytesExt};
+    use std::io::{self, Read, Write};
+    use std::net::Ipv6Addr;
+
+    pub fn read_from<O: ByteOrder, B: Read + PositionAware>(
+        buffer: &mut B,
+        _: (),
+    ) -> io::Result<Ipv6Addr> {
+        buffer.read_u128::<O>().map(Ipv6Addr::from)
+    }
+
+    pub fn write_to<O: ByteOrder, B: Write + PositionAware>(
+        value: &Ipv6Addr,
+        buffer: &mut B,
+        _: (),
+    ) -> io::Result<()> {
+        buffer.write_u128::<O>(u128::from(*value))
+    }
+
+    pub fn encoded_len<O>(_: &Ipv6Addr, _: ()) -> usize {
+        16
+    }
+}